@@ -103,6 +103,12 @@ impl FishLibrary {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct SteamId(pub(crate) u64);
 
+impl SteamId {
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LobbyId(pub(crate) u64);
 
@@ -1,7 +1,9 @@
 mod network_replicate;
 mod physics_object;
+mod snapshot;
 mod weapon;
 
 pub use network_replicate::NetworkReplicate;
 pub use physics_object::{PhysicsObject, PhysicsObjectTrait};
+pub use snapshot::{add_simulated_node, Snapshot};
 pub use weapon::{Weapon, WeaponTrait};
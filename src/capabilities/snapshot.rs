@@ -0,0 +1,41 @@
+use macroquad::experimental::scene::{self, HandleUntyped, Node};
+
+use crate::{
+    capabilities::NetworkReplicate,
+    snapshot::{SnapshotReader, SnapshotWriter},
+};
+
+/// Anything that can save its simulation state and get back to it later
+/// Used by the rollback networking to re-simulate frames with corrected inputs
+/// All the nodes with "NetworkReplicate" should provide it as well
+#[derive(Clone, Copy)]
+pub struct Snapshot {
+    /// Write everything that may affect the simulation
+    /// Purely visual state, like particles or sounds, may be skipped
+    pub save: fn(node: HandleUntyped, writer: &mut SnapshotWriter),
+    /// Overwrite the node state with the data written by "save"
+    /// Data should be read in exactly the same order it was written
+    pub load: fn(node: HandleUntyped, reader: &mut SnapshotReader),
+    /// Create a node of the same kind, "load" will be called on it right after,
+    /// so usually it is just the node's "spawn" with any arguments
+    /// Needed for nodes that may be deleted in the middle of the game, like bullets
+    /// None for nodes living through the whole match
+    pub rebuild: Option<fn() -> HandleUntyped>,
+}
+
+/// Add a node that takes part in the simulation right away
+/// "ready" is called only on the next scene update, so nodes spawned in the middle
+/// of the simulation or rebuilt from a snapshot get their capabilities here instead
+pub fn add_simulated_node<T: Node + 'static>(
+    node: T,
+    network: NetworkReplicate,
+    snapshot: Snapshot,
+) -> HandleUntyped {
+    let handle = scene::add_node(node);
+
+    let mut node = scene::get_node(handle);
+    node.provides(network);
+    node.provides(snapshot);
+
+    handle.untyped()
+}
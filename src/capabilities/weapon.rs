@@ -1,10 +1,7 @@
-use crate::nodes::Player;
+use crate::{input::Aim, nodes::Player};
 
 use macroquad::{
-    experimental::{
-        coroutines::Coroutine,
-        scene::{CapabilityTrait, Handle, HandleUntyped, NodeWith},
-    },
+    experimental::scene::{CapabilityTrait, Handle, HandleUntyped, NodeWith},
     math::{Rect, Vec2},
 };
 
@@ -25,8 +22,19 @@ pub struct Weapon {
     /// "mount" is being called to attach the weapon to a fish
     /// in other words - mount should move a weapon to a mount point on a fish
     pub mount: fn(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool),
+    /// Called right after "mount" with the owner's aim, None is "straight ahead"
+    /// Weapons that can't aim use Weapon::no_aim
+    pub aim: fn(node: HandleUntyped, aim: Option<Aim>),
     pub is_thrown: fn(node: HandleUntyped) -> bool,
     pub pick_up: fn(node: HandleUntyped, owner: Handle<Player>),
     pub throw: fn(node: HandleUntyped, force: bool),
-    pub shoot: fn(node: HandleUntyped, player: Handle<Player>) -> Coroutine,
+    /// Called once per simulation frame while the owner is in the shoot state,
+    /// "frame" counts from 0. Returns true when the shot is over.
+    /// Everything the shot needs to remember between frames should be a part
+    /// of the weapon's snapshot, so a rollback can resume it.
+    pub shoot: fn(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool,
+}
+
+impl Weapon {
+    pub fn no_aim(_node: HandleUntyped, _aim: Option<Aim>) {}
 }
@@ -9,12 +9,16 @@
 //! results from component's calls
 //! Or just copy-paste the whole component code into a weapon and modify it. This is fine!
 
+mod ammo;
 mod bullet;
 mod gunlike_animation;
 mod physics_body;
+mod simulated_sprite;
 mod throwable_item;
 
+pub use ammo::{Ammo, AmmoHud, WhenEmpty};
 pub use bullet::Bullet;
 pub use gunlike_animation::GunlikeAnimation;
 pub use physics_body::PhysicsBody;
+pub use simulated_sprite::SimulatedSprite;
 pub use throwable_item::ThrowableItem;
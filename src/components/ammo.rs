@@ -0,0 +1,151 @@
+use macroquad::prelude::*;
+
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// How the ammo is shown above the weapon while it is held
+#[derive(Clone, Copy, PartialEq)]
+pub enum AmmoHud {
+    Circles,
+    Lines,
+    Bar,
+    Hidden,
+}
+
+impl AmmoHud {
+    /// "circles", "lines" or "bar", anything else - no HUD
+    pub fn from_name(name: &str) -> AmmoHud {
+        match name {
+            "circles" => AmmoHud::Circles,
+            "lines" => AmmoHud::Lines,
+            "bar" => AmmoHud::Bar,
+            _ => AmmoHud::Hidden,
+        }
+    }
+}
+
+/// What happens to the weapon after the last shot
+#[derive(Clone, Copy, PartialEq)]
+pub enum WhenEmpty {
+    /// Stays in the fish hands, clicking
+    Keep,
+    /// Falls down, same as "down + throw"
+    Drop,
+    /// Thrown forward, same as "throw"
+    Throw,
+}
+
+impl WhenEmpty {
+    /// "drop" or "throw", anything else - keep
+    pub fn from_name(name: &str) -> WhenEmpty {
+        match name {
+            "drop" => WhenEmpty::Drop,
+            "throw" => WhenEmpty::Throw,
+            _ => WhenEmpty::Keep,
+        }
+    }
+}
+
+/// Shots left in a weapon
+/// The count stays with the weapon: it starts full when spawned, and an
+/// empty weapon (WhenEmpty::Keep) picked up again is still empty
+pub struct Ammo {
+    pub left: i32,
+    pub magazine: i32,
+    pub hud: AmmoHud,
+    pub when_empty: WhenEmpty,
+}
+
+impl Ammo {
+    pub fn new(magazine: i32, hud: AmmoHud, when_empty: WhenEmpty) -> Ammo {
+        Ammo {
+            left: magazine,
+            magazine,
+            hud,
+            when_empty,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left <= 0
+    }
+
+    pub fn reload(&mut self) {
+        self.left = self.magazine;
+    }
+
+    /// Returns false if there was nothing to take
+    pub fn take(&mut self) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        self.left -= 1;
+        true
+    }
+
+    /// After the last shot: Some(force) if the weapon should be thrown away
+    /// with "throw(force)", None if it is kept
+    pub fn throw_away(&self) -> Option<bool> {
+        match self.when_empty {
+            _ if !self.is_empty() => None,
+            WhenEmpty::Keep => None,
+            WhenEmpty::Drop => Some(false),
+            WhenEmpty::Throw => Some(true),
+        }
+    }
+
+    pub fn save(&self, writer: &mut SnapshotWriter) {
+        writer.write(&self.left);
+    }
+
+    pub fn load(&mut self, reader: &mut SnapshotReader) {
+        self.left = reader.read();
+    }
+
+    /// "pos" is the weapon position, HUD is drawn right above it
+    pub fn draw_hud(&self, pos: Vec2) {
+        let full_color = Color::new(0.8, 0.9, 1.0, 1.0);
+        let empty_color = Color::new(0.8, 0.9, 1.0, 0.8);
+
+        match self.hud {
+            AmmoHud::Circles => {
+                for i in 0..self.magazine {
+                    let x = pos.x + 15.0 * i as f32;
+
+                    if i >= self.left {
+                        draw_circle_lines(x, pos.y - 12.0, 4.0, 2., empty_color);
+                    } else {
+                        draw_circle(x, pos.y - 12.0, 4.0, full_color);
+                    };
+                }
+            }
+            AmmoHud::Lines => {
+                let line_height = 16.0;
+                let line_spacing = 1.0;
+                let line_thickness = 2.0;
+
+                for i in 0..self.magazine {
+                    let x = pos.x - 15.0 + (line_thickness + line_spacing) * i as f32;
+                    let y = pos.y - 12.0;
+                    let color = if i >= self.left {
+                        empty_color
+                    } else {
+                        full_color
+                    };
+
+                    draw_line(x, y, x, y - line_height, line_thickness, color);
+                }
+            }
+            AmmoHud::Bar => {
+                let width = 40.0;
+                let height = 4.0;
+                let y = pos.y - 12.0 - height;
+                let full = width * self.left.max(0) as f32 / self.magazine as f32;
+
+                draw_rectangle_lines(pos.x, y, width, height, 1., empty_color);
+                draw_rectangle(pos.x, y, full, height, full_color);
+            }
+            AmmoHud::Hidden => {}
+        }
+    }
+}
@@ -1,12 +1,14 @@
 use macroquad::{
     experimental::{collections::storage, scene},
     math::{vec2, Vec2},
-    rand,
-    time::get_frame_time,
 };
 use macroquad_platformer::Tile;
 
-use crate::Resources;
+use crate::{
+    simulation::{self, FRAME_TIME},
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
 
 pub struct Bullet {
     pub pos: Vec2,
@@ -14,13 +16,24 @@ pub struct Bullet {
     pub lived: f32,
     pub lifetime: f32,
     pub spread: f32,
+    /// Id of the fish who fired it, to credit the kill
+    pub owner: u8,
 }
 
 impl Bullet {
-    pub fn new(pos: Vec2, lifetime: f32, facing: bool, speed: f32, spread: f32) -> Bullet {
-        let y = rand::gen_range(-spread, spread);
+    pub fn new(
+        pos: Vec2,
+        lifetime: f32,
+        direction: Vec2,
+        speed: f32,
+        spread: f32,
+        owner: u8,
+    ) -> Bullet {
+        let y = simulation::gen_range(-spread, spread);
 
-        let dir = if facing { vec2(1.0, y) } else { vec2(-1.0, y) };
+        // spread is across the direction, "down" for both left and right
+        let normal = vec2(-direction.y, direction.x) * direction.x.signum();
+        let dir = direction + normal * y;
 
         Bullet {
             pos,
@@ -28,12 +41,27 @@ impl Bullet {
             lived: 0.,
             lifetime,
             spread,
+            owner,
         }
     }
 
+    pub fn save(&self, writer: &mut SnapshotWriter) {
+        writer.write_vec2(self.pos);
+        writer.write_vec2(self.speed);
+        writer.write(&self.lived);
+        writer.write(&self.owner);
+    }
+
+    pub fn load(&mut self, reader: &mut SnapshotReader) {
+        self.pos = reader.read_vec2();
+        self.speed = reader.read_vec2();
+        self.lived = reader.read();
+        self.owner = reader.read();
+    }
+
     pub fn update(&mut self) -> bool {
-        self.pos += self.speed * get_frame_time();
-        self.lived += get_frame_time();
+        self.pos += self.speed * FRAME_TIME;
+        self.lived += FRAME_TIME;
 
         if self.lived > self.lifetime {
             return false;
@@ -43,16 +71,30 @@ impl Bullet {
             if player.get_hitbox().contains(self.pos) {
                 let direction = self.pos.x > (player.body.pos.x + 10.);
 
+                // the shield takes the bullet instead of the fish
+                if direction == player.body.facing && player.front_armor > 0 {
+                    player.front_armor -= 1;
+
+                    let mut resources = storage::get_mut::<Resources>();
+                    if let Some(effects) = resources.effects.as_mut() {
+                        effects.hit_fxses.spawn(self.pos);
+                    }
+
+                    return false;
+                }
+
                 scene::find_node_by_type::<crate::nodes::Camera>()
                     .unwrap()
                     .shake_noise(1.0, 10, 1.);
 
                 {
                     let mut resources = storage::get_mut::<Resources>();
-                    resources.hit_fxses.spawn(self.pos);
+                    if let Some(effects) = resources.effects.as_mut() {
+                        effects.hit_fxses.spawn(self.pos);
+                    }
                 }
 
-                player.kill(direction);
+                player.kill(direction, Some(self.owner));
 
                 return false;
             }
@@ -60,7 +102,9 @@ impl Bullet {
 
         let mut resources = storage::get_mut::<Resources>();
         if resources.collision_world.collide_solids(self.pos, 5, 5) == Tile::Solid {
-            resources.hit_fxses.spawn(self.pos);
+            if let Some(effects) = resources.effects.as_mut() {
+                effects.hit_fxses.spawn(self.pos);
+            }
             return false;
         }
 
@@ -18,20 +18,24 @@
 
 use macroquad::{
     color,
-    experimental::animation::AnimatedSprite,
     math::Vec2,
     texture::{draw_texture_ex, DrawTextureParams, Texture2D},
 };
 
+use crate::{
+    components::SimulatedSprite,
+    snapshot::{SnapshotReader, SnapshotWriter},
+};
+
 pub struct GunlikeAnimation {
-    sprite: AnimatedSprite,
+    sprite: SimulatedSprite,
     spritesheet: Texture2D,
     collider_width: f32,
 }
 
 impl GunlikeAnimation {
     pub fn new(
-        sprite: AnimatedSprite,
+        sprite: SimulatedSprite,
         spritesheet: Texture2D,
         collider_width: f32,
     ) -> GunlikeAnimation {
@@ -71,4 +75,12 @@ impl GunlikeAnimation {
     pub fn set_frame(&mut self, frame: u32) {
         self.sprite.set_frame(frame);
     }
+
+    pub fn save(&self, writer: &mut SnapshotWriter) {
+        self.sprite.save(writer);
+    }
+
+    pub fn load(&mut self, reader: &mut SnapshotReader) {
+        self.sprite.load(reader);
+    }
 }
@@ -1,12 +1,15 @@
 use macroquad::{
     experimental::collections::storage,
     math::{vec2, Vec2},
-    time::get_frame_time,
 };
 
-use macroquad_platformer::{Actor, World as CollisionWorld};
+use macroquad_platformer::{Actor, Tile, World as CollisionWorld};
 
-use crate::Resources;
+use crate::{
+    simulation::FRAME_TIME,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
 
 pub struct PhysicsBody {
     pub pos: Vec2,
@@ -19,6 +22,11 @@ pub struct PhysicsBody {
     pub last_frame_on_ground: bool,
     pub have_gravity: bool,
     pub bouncyness: f32,
+    /// Sub-pixel movement leftovers kept inside the collision world
+    /// Collision world does not expose them, so they are mirrored here for snapshots
+    pub collider_remainder: Vec2,
+    /// "descent" was requested, but collision world did not move the actor yet
+    pub descent_requested: bool,
 }
 
 impl PhysicsBody {
@@ -41,6 +49,8 @@ impl PhysicsBody {
             on_ground: false,
             have_gravity: true,
             bouncyness: 0.0,
+            collider_remainder: vec2(0., 0.),
+            descent_requested: false,
         }
     }
 
@@ -56,6 +66,16 @@ impl PhysicsBody {
         let collision_world = &mut storage::get_mut::<Resources>().collision_world;
 
         collision_world.descent(self.collider);
+        self.descent_requested = true;
+    }
+
+    /// Put both the body and its collider to the given position
+    pub fn teleport(&mut self, pos: Vec2) {
+        let collision_world = &mut storage::get_mut::<Resources>().collision_world;
+
+        self.pos = pos;
+        self.collider_remainder = vec2(0., 0.);
+        collision_world.set_actor_position(self.collider, pos);
     }
 
     pub fn update(&mut self) {
@@ -65,22 +85,77 @@ impl PhysicsBody {
         self.last_frame_on_ground = self.on_ground;
         self.on_ground = collision_world.collide_check(self.collider, self.pos + vec2(0., 1.));
         if !self.on_ground && self.have_gravity {
-            self.speed.y += Self::GRAVITY * get_frame_time();
+            self.speed.y += Self::GRAVITY * FRAME_TIME;
         }
-        if !collision_world.move_h(self.collider, self.speed.x * get_frame_time()) {
+
+        // exactly the same math the collision world does with its remainders
+        let dx = self.speed.x * FRAME_TIME;
+        self.collider_remainder.x += dx;
+        self.collider_remainder.x -= self.collider_remainder.x.round() as i32 as f32;
+        if !collision_world.move_h(self.collider, dx) {
             self.speed.x *= -self.bouncyness;
         }
-        if !collision_world.move_v(self.collider, self.speed.y * get_frame_time()) {
+
+        let dy = self.speed.y * FRAME_TIME;
+        self.collider_remainder.y += dy;
+        self.collider_remainder.y -= self.collider_remainder.y.round() as i32 as f32;
+        if !collision_world.move_v(self.collider, dy) {
             self.speed.y *= -self.bouncyness;
         }
+        self.descent_requested = false;
+
         self.pos = collision_world.actor_pos(self.collider);
     }
 
+    pub fn save(&self, writer: &mut SnapshotWriter) {
+        writer.write_vec2(self.pos);
+        writer.write_vec2(self.speed);
+        writer.write(&self.facing);
+        writer.write(&self.angle);
+        writer.write(&self.on_ground);
+        writer.write(&self.last_frame_on_ground);
+        writer.write(&self.have_gravity);
+        writer.write(&self.bouncyness);
+        writer.write_vec2(self.collider_remainder);
+        writer.write(&self.descent_requested);
+    }
+
+    pub fn load(&mut self, reader: &mut SnapshotReader) {
+        self.pos = reader.read_vec2();
+        self.speed = reader.read_vec2();
+        self.facing = reader.read();
+        self.angle = reader.read();
+        self.on_ground = reader.read();
+        self.last_frame_on_ground = reader.read();
+        self.have_gravity = reader.read();
+        self.bouncyness = reader.read();
+        let remainder = reader.read_vec2();
+        self.descent_requested = reader.read();
+
+        self.teleport(self.pos);
+
+        // remainders are always less than half a pixel, so this will not
+        // move the actor, only bring the remainders back
+        let collision_world = &mut storage::get_mut::<Resources>().collision_world;
+        self.collider_remainder = remainder;
+        collision_world.move_h(self.collider, remainder.x);
+        collision_world.move_v(self.collider, remainder.y);
+
+        // "descent" flag of the collision world is not observable, but it is
+        // always set while the actor is inside a jump-through platform
+        let inside_platform =
+            collision_world.collide_solids(self.pos, self.size.x as i32, self.size.y as i32)
+                == Tile::JumpThrough;
+        if self.descent_requested || inside_platform {
+            collision_world.descent(self.collider);
+        }
+    }
+
     pub fn update_throw(&mut self) {
         if !self.on_ground {
             self.angle += self.speed.x.abs() * 0.00045 + self.speed.y.abs() * 0.00015;
 
-            self.speed.y += Self::GRAVITY * get_frame_time();
+            self.speed.y += Self::GRAVITY * FRAME_TIME;
         } else {
             self.angle %= std::f32::consts::PI * 2.;
             let goal = if self.angle <= std::f32::consts::PI {
@@ -0,0 +1,103 @@
+//! AnimatedSprite counting simulation frames instead of the real time
+//!
+//! macroquad's AnimatedSprite advances with get_frame_time and keeps its timer private,
+//! so it neither runs the same way on all the peers nor fits into a snapshot.
+//! Sprites updated from "network_update" should be this one, the API is the same.
+
+use macroquad::{
+    experimental::animation::{Animation, AnimationFrame},
+    math::{vec2, Rect},
+};
+
+use crate::{
+    simulation::FRAME_TIME,
+    snapshot::{SnapshotReader, SnapshotWriter},
+};
+
+pub struct SimulatedSprite {
+    tile_width: f32,
+    tile_height: f32,
+    animations: Vec<Animation>,
+
+    current_animation: usize,
+    /// Simulation frames since the last sprite frame change
+    ticks: u32,
+    frame: u32,
+    pub playing: bool,
+}
+
+impl SimulatedSprite {
+    pub fn new(
+        tile_width: u32,
+        tile_height: u32,
+        animations: &[Animation],
+        playing: bool,
+    ) -> SimulatedSprite {
+        SimulatedSprite {
+            tile_width: tile_width as f32,
+            tile_height: tile_height as f32,
+            animations: animations.to_vec(),
+            current_animation: 0,
+            ticks: 0,
+            frame: 0,
+            playing,
+        }
+    }
+
+    pub fn set_animation(&mut self, animation: usize) {
+        self.current_animation = animation;
+
+        let animation = &self.animations[self.current_animation];
+        self.frame %= animation.frames;
+    }
+
+    pub fn current_animation(&self) -> usize {
+        self.current_animation
+    }
+
+    pub fn set_frame(&mut self, frame: u32) {
+        self.frame = frame;
+    }
+
+    /// Should be called exactly once per simulation frame
+    pub fn update(&mut self) {
+        let animation = &self.animations[self.current_animation];
+
+        if self.playing {
+            self.ticks += 1;
+            if self.ticks as f32 * FRAME_TIME > 1. / animation.fps as f32 {
+                self.frame += 1;
+                self.ticks = 0;
+            }
+        }
+        self.frame %= animation.frames;
+    }
+
+    pub fn frame(&self) -> AnimationFrame {
+        let animation = &self.animations[self.current_animation];
+
+        AnimationFrame {
+            source_rect: Rect::new(
+                self.tile_width * self.frame as f32,
+                self.tile_height * animation.row as f32,
+                self.tile_width,
+                self.tile_height,
+            ),
+            dest_size: vec2(self.tile_width, self.tile_height),
+        }
+    }
+
+    pub fn save(&self, writer: &mut SnapshotWriter) {
+        writer.write(&(self.current_animation as u32));
+        writer.write(&self.ticks);
+        writer.write(&self.frame);
+        writer.write(&self.playing);
+    }
+
+    pub fn load(&mut self, reader: &mut SnapshotReader) {
+        self.current_animation = reader.read::<u32>() as usize;
+        self.ticks = reader.read();
+        self.frame = reader.read();
+        self.playing = reader.read();
+    }
+}
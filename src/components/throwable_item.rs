@@ -1,12 +1,13 @@
 use macroquad::{
-    experimental::{
-        collections::storage,
-        scene::{self, Handle},
-    },
+    experimental::scene::{self, Handle},
     math::{vec2, Rect},
 };
 
-use crate::{components::PhysicsBody, nodes::Player, Resources};
+use crate::{
+    components::PhysicsBody,
+    nodes::Player,
+    snapshot::{SnapshotReader, SnapshotWriter},
+};
 
 #[derive(Default)]
 pub struct ThrowableItem {
@@ -31,11 +32,17 @@ impl ThrowableItem {
             body.angle = 3.5;
         }
 
-        let mut resources = storage::get_mut::<Resources>();
+        body.teleport(body.pos);
+    }
+
+    pub fn save(&self, writer: &mut SnapshotWriter) {
+        writer.write_node(self.owner.map(|owner| owner.untyped()));
+    }
 
-        resources
-            .collision_world
-            .set_actor_position(body.collider, body.pos);
+    pub fn load(&mut self, reader: &mut SnapshotReader) {
+        self.owner = reader
+            .read_node()
+            .map(|owner| scene::get_untyped_node(owner).unwrap().handle::<Player>());
     }
 
     pub fn update(&mut self, body: &mut PhysicsBody, disarm: bool) {
@@ -0,0 +1,230 @@
+//! Automatic determinism check for the items, see "Item::network_ready"
+//!
+//! Each item is spawned right next to a fish and the same scripted match is
+//! simulated twice from scratch. Everything network peers are allowed to disagree on
+//! is different between the two runs: macroquad's global random is seeded differently,
+//! and the first run is a tight loop, while the second one lets macroquad go through
+//! a real frame (scene update and draw, a new real frame time) every few simulation
+//! frames. An item relying on any of it will end up with a different world state hash.
+//!
+//! Rollbacks are checked the same way: a match is rolled back halfway through
+//! the states that take many frames (a fish dying, sliding or swinging a sword,
+//! a sproinger bouncing) and should end up exactly where the straight run did.
+//!
+//! Run with "fishfight --check-items", the process exits with an error if any
+//! "network_ready" item diverged or any rollback did not match.
+//! The match is simulated headless, see "headless" module.
+
+use macroquad::{
+    experimental::{collections::storage, scene},
+    prelude::*,
+};
+
+use crate::{
+    headless::{Headless, FLOOR_Y, PLAYER1_POS},
+    items::{Item, ItemRegistry, Sproinger},
+    nodes::Player,
+    snapshot::WorldSnapshot,
+    Input,
+};
+
+const MAP: &str = "assets/levels/lev01.json";
+const SEED: u64 = 42;
+const FRAMES: usize = 300;
+
+/// The same script for every item: pick it up, use it few times,
+/// run around and throw it away
+fn scripted_input(frame: usize) -> Input {
+    let mut input = Input::default();
+
+    match frame {
+        30 | 240 => input.throw = true,
+        60 | 70 | 80 | 90 | 100 | 110 => input.fire = true,
+        120..=179 => {
+            input.right = true;
+            input.jump = (130..140).contains(&frame);
+        }
+        180..=239 => {
+            input.left = true;
+            input.fire = frame == 200;
+        }
+        _ => {}
+    }
+
+    input
+}
+
+/// A real frame after each REAL_FRAME_PACE simulation frames, when those are on
+const REAL_FRAME_PACE: usize = 3;
+
+/// Run the whole scripted match, returns world hash for each frame
+/// "real_frames" - let macroquad have real frames in between, see REAL_FRAME_PACE
+async fn simulate(item: &Item, global_seed: u64, real_frames: bool) -> Vec<u64> {
+    rand::srand(global_seed);
+
+    let headless = Headless::new(MAP, 2, SEED).await;
+
+    let pos = headless.player(0).body.pos;
+    (item.constructor)(pos);
+
+    headless.ready().await;
+
+    let mut hashes = Vec::with_capacity(FRAMES);
+    for frame in 0..FRAMES {
+        headless.step(&[scripted_input(frame), Input::default()]);
+        if real_frames && frame.is_multiple_of(REAL_FRAME_PACE) {
+            next_frame().await;
+        }
+
+        hashes.push(WorldSnapshot::save().checksum());
+    }
+
+    hashes
+}
+
+/// Returns false if any "network_ready" item turned out to be non-deterministic
+pub async fn check_items() -> bool {
+    let mut success = true;
+
+    let registry = storage::get::<ItemRegistry>();
+    for item in registry.iter() {
+        let first = simulate(item, 1, false).await;
+        let second = simulate(item, 2, true).await;
+
+        match first.iter().zip(&second).position(|(a, b)| a != b) {
+            None => println!("{}: deterministic", item.tiled_name),
+            Some(frame) if item.network_ready => {
+                println!(
+                    "{}: diverged on frame {}, but marked as network_ready!",
+                    item.tiled_name, frame
+                );
+                success = false;
+            }
+            Some(frame) => println!(
+                "{}: diverged on frame {}, not allowed in network games",
+                item.tiled_name, frame
+            ),
+        }
+    }
+
+    success && check_rollbacks().await
+}
+
+const ROLLBACK_FRAMES: usize = 200;
+/// Frames simulated with the wrong input before rolling back, like after a misprediction
+const MISPREDICTED_FRAMES: usize = 8;
+
+struct Rollback {
+    name: &'static str,
+    frame: usize,
+    /// Should be true on the rollback frame, otherwise the script went wrong
+    /// and the rollback checks nothing
+    happening: fn(&Headless) -> bool,
+}
+
+const ROLLBACKS: &[Rollback] = &[
+    Rollback {
+        name: "shot",
+        frame: 56,
+        happening: |headless| headless.player(0).state() == Player::ST_SHOOT,
+    },
+    Rollback {
+        name: "slide",
+        frame: 85,
+        happening: |headless| headless.player(0).state() == Player::ST_SLIDE,
+    },
+    Rollback {
+        name: "death",
+        frame: 100,
+        happening: |headless| headless.player(1).state() == Player::ST_DEATH,
+    },
+    Rollback {
+        name: "sproing",
+        frame: 150,
+        happening: |_| {
+            let sproinger = scene::find_node_by_type::<Sproinger>().unwrap();
+            sproinger.state() == Sproinger::ST_JUMP
+        },
+    },
+];
+
+/// Pick up a sword, kill the other fish with it, slide and jump on a sproinger
+fn rollback_input(frame: usize) -> [Input; 2] {
+    let input = Input {
+        throw: frame == 40,
+        fire: frame == 50,
+        down: (80..90).contains(&frame),
+        slide: frame == 82,
+        left: (100..160).contains(&frame),
+        ..Default::default()
+    };
+
+    [input, Input::default()]
+}
+
+/// World hash for each frame, None if the fish was not in the expected state
+async fn simulate_rollback(rollback: Option<&Rollback>) -> Option<Vec<u64>> {
+    let headless = Headless::new(MAP, 2, SEED).await;
+    headless.player(0).body.teleport(PLAYER1_POS);
+    headless
+        .player(1)
+        .body
+        .teleport(PLAYER1_POS + vec2(50., 0.));
+    headless.spawn_item("sword", PLAYER1_POS);
+    headless.spawn_item("sproinger", vec2(PLAYER1_POS.x - 100., FLOOR_Y - 20.));
+    headless.ready().await;
+
+    let mut hashes = Vec::with_capacity(ROLLBACK_FRAMES);
+    for frame in 0..ROLLBACK_FRAMES {
+        if let Some(rollback) = rollback.filter(|rollback| rollback.frame == frame) {
+            if !(rollback.happening)(&headless) {
+                return None;
+            }
+
+            let snapshot = WorldSnapshot::save();
+            for _ in 0..MISPREDICTED_FRAMES {
+                headless.step(&[Input::default(), Input::default()]);
+            }
+            snapshot.load();
+        }
+
+        headless.step(&rollback_input(frame));
+
+        hashes.push(WorldSnapshot::save().checksum());
+    }
+
+    Some(hashes)
+}
+
+/// Returns false if any rollback ended up somewhere else than the straight run
+async fn check_rollbacks() -> bool {
+    let mut success = true;
+
+    let straight = simulate_rollback(None).await.unwrap();
+    for rollback in ROLLBACKS {
+        let hashes = match simulate_rollback(Some(rollback)).await {
+            Some(hashes) => hashes,
+            None => {
+                println!(
+                    "rollback mid-{}: not happening on frame {}, script is broken",
+                    rollback.name, rollback.frame
+                );
+                success = false;
+                continue;
+            }
+        };
+
+        match straight.iter().zip(&hashes).position(|(a, b)| a != b) {
+            None => println!("rollback mid-{}: ok", rollback.name),
+            Some(frame) => {
+                println!(
+                    "rollback mid-{}: diverged on frame {}",
+                    rollback.name, frame
+                );
+                success = false;
+            }
+        }
+    }
+
+    success
+}
@@ -1,3 +1,5 @@
+pub mod controls;
+pub mod intermission;
 pub mod main_menu;
 pub mod pause_menu;
 mod style;
@@ -43,4 +45,9 @@ impl GuiResources {
             levels,
         }
     }
+
+    /// All the maps from levels.toml, in the same order
+    pub fn level_maps(&self) -> Vec<String> {
+        self.levels.iter().map(|level| level.map.clone()).collect()
+    }
 }
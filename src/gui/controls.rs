@@ -0,0 +1,162 @@
+//! Controls tab of the main menu: rebinding the actions of each input scheme
+//! Changes are only applied and written to config/input.toml on "Save"
+
+use macroquad::{
+    experimental::collections::storage,
+    prelude::*,
+    ui::{self, hash, widgets},
+};
+
+use crate::{
+    gui::GuiResources,
+    input::{Binding, Bindings, InputScheme, ACTIONS, BUTTONS, CONFIG_PATH},
+};
+
+pub struct ControlsUiState {
+    /// Edited copy, not used by the game until saved
+    bindings: Bindings,
+    /// Index in SCHEMES
+    scheme: usize,
+    /// Action waiting for a key or a button, index in ACTIONS, and which of
+    /// its bindings will be replaced, None to add one more
+    waiting: Option<(usize, Option<usize>)>,
+    status: String,
+}
+
+const SCHEMES: [(&str, InputScheme); 3] = [
+    ("Keyboard 1", InputScheme::KeyboardLeft),
+    ("Keyboard 2", InputScheme::KeyboardRight),
+    ("Gamepads", InputScheme::Gamepad(0)),
+];
+
+impl ControlsUiState {
+    pub fn new() -> ControlsUiState {
+        ControlsUiState {
+            bindings: storage::get::<Bindings>().clone(),
+            scheme: 0,
+            waiting: None,
+            status: String::new(),
+        }
+    }
+
+    /// Keys pressed while waiting are bindings, not menu navigation
+    pub fn is_waiting(&self) -> bool {
+        self.waiting.is_some()
+    }
+}
+
+/// Key, button or stick pressed this frame, if any
+fn pressed_binding(gui_resources: &GuiResources, deadzone: f32) -> Option<Binding> {
+    if let Some(key) = get_last_key_pressed() {
+        return Some(Binding::Key(key));
+    }
+
+    for ix in 0..quad_gamepad::MAX_DEVICES {
+        let state = gui_resources.gamepads.state(ix);
+        if state.status != quad_gamepad::ControllerStatus::Connected {
+            continue;
+        }
+
+        for button in BUTTONS {
+            let button_ix = *button as usize;
+            if state.digital_state[button_ix] && !state.digital_state_prev[button_ix] {
+                return Some(Binding::Button(*button));
+            }
+        }
+        for (axis, value) in state.analog_state.iter().enumerate() {
+            if value.abs() > deadzone {
+                return Some(Binding::Axis(axis, *value > 0.));
+            }
+        }
+    }
+
+    None
+}
+
+pub fn controls_ui(ui: &mut ui::Ui, state: &mut ControlsUiState) {
+    let names = SCHEMES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    widgets::ComboBox::new(hash!(), &names)
+        .ratio(0.4)
+        .label("Controls of")
+        .ui(ui, &mut state.scheme);
+
+    let scheme = state.bindings.scheme_mut(SCHEMES[state.scheme].1);
+
+    if let Some((action, slot)) = state.waiting {
+        ui.label(
+            None,
+            &format!(
+                "Press a key or a gamepad button for \"{}\", Escape to cancel{}",
+                ACTIONS[action],
+                if slot.is_some() {
+                    ", Delete to remove"
+                } else {
+                    ""
+                }
+            ),
+        );
+
+        if is_key_pressed(KeyCode::Escape) {
+            state.waiting = None;
+        } else if is_key_pressed(KeyCode::Delete) {
+            if let Some(slot) = slot {
+                scheme.actions[action].remove(slot);
+            }
+            state.waiting = None;
+        } else {
+            let gui_resources = storage::get::<GuiResources>();
+            if let Some(binding) = pressed_binding(&gui_resources, scheme.deadzone) {
+                match slot {
+                    Some(slot) => scheme.actions[action][slot] = binding,
+                    None => scheme.actions[action].push(binding),
+                }
+                state.waiting = None;
+            }
+        }
+    } else {
+        ui.label(None, "Click a binding to replace it, \"+\" to add one more");
+    }
+    ui.separator();
+
+    for (ix, action) in ACTIONS.iter().enumerate() {
+        ui.label(None, &format!("{}:", action));
+
+        for (slot, binding) in scheme.actions[ix].iter().enumerate() {
+            ui.same_line(0.);
+            if ui.button(None, binding.name().as_str()) {
+                state.waiting = Some((ix, Some(slot)));
+            }
+        }
+        ui.same_line(0.);
+        if ui.button(None, "+") {
+            state.waiting = Some((ix, None));
+        }
+    }
+    let aim = scheme
+        .aim
+        .map(|aim| aim.name())
+        .unwrap_or_else(|| "none".to_string());
+    ui.label(None, &format!("aim: {}, only in {}", aim, CONFIG_PATH));
+    ui.separator();
+
+    let conflicts = state.bindings.conflicts();
+    for conflict in &conflicts {
+        ui.label(None, &format!("Conflict: {}", conflict));
+    }
+
+    if conflicts.is_empty() && ui.button(None, "Save") {
+        *storage::get_mut::<Bindings>() = state.bindings.clone();
+
+        state.status = match state.bindings.save() {
+            Ok(()) => "Saved".to_string(),
+            Err(err) => format!("Can't save: {}", err),
+        };
+    }
+    ui.same_line(0.);
+    if ui.button(None, "Defaults") {
+        state.bindings = Bindings::default();
+        state.status.clear();
+    }
+
+    ui.label(None, &state.status);
+}
@@ -0,0 +1,88 @@
+use macroquad::{experimental::collections::storage, prelude::*};
+
+use crate::{
+    gui::GuiResources,
+    nodes::{GameState, MatchRules},
+};
+
+const INTERMISSION_TIME: f64 = 5.;
+
+fn skip_pressed(gui_resources: &GuiResources) -> bool {
+    if is_key_pressed(KeyCode::Enter) {
+        return true;
+    }
+
+    (0..quad_gamepad::MAX_DEVICES).any(|ix| {
+        let state = gui_resources.gamepads.state(ix);
+        let a = quad_gamepad::GamepadButton::A as usize;
+
+        state.digital_state[a] && !state.digital_state_prev[a]
+    })
+}
+
+/// Score between the rounds
+/// Network peers can't skip it, so nobody waits too long for the others
+/// in the next round
+pub async fn scoreboard(
+    round_wins: &[u32],
+    rules: MatchRules,
+    rounds_played: usize,
+    next_map: &str,
+    skippable: bool,
+) {
+    let end_time = get_time() + INTERMISSION_TIME;
+
+    // skip a frame to let Enter be unpressed from the previous screen
+    next_frame().await;
+
+    while get_time() < end_time {
+        {
+            let mut gui_resources = storage::get_mut::<GuiResources>();
+            gui_resources.gamepads.update();
+
+            if skippable && skip_pressed(&gui_resources) {
+                break;
+            }
+        }
+
+        clear_background(BLACK);
+
+        let x = screen_width() / 2. - 200.;
+        let mut y = screen_height() / 2. - 100.;
+        draw_text(&format!("After round {}", rounds_played), x, y, 40., WHITE);
+
+        for (id, wins) in round_wins.iter().enumerate() {
+            y += 40.;
+            draw_text(
+                &format!("P{}: {}/{} rounds", id + 1, wins, rules.rounds_to_win),
+                x,
+                y,
+                40.,
+                GameState::player_color(id as u8),
+            );
+        }
+
+        let level = next_map
+            .rsplit('/')
+            .next()
+            .unwrap()
+            .trim_end_matches(".json");
+        y += 60.;
+        draw_text(
+            &format!(
+                "Next: {} in {}",
+                level,
+                (end_time - get_time()).ceil() as i32
+            ),
+            x,
+            y,
+            30.,
+            GRAY,
+        );
+        if skippable {
+            draw_text("(A) (Enter) to go now", x, y + 30., 30., GRAY);
+        }
+
+        next_frame().await;
+    }
+}
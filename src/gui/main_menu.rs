@@ -4,17 +4,75 @@ use macroquad::{
     ui::{self, hash, root_ui, widgets},
 };
 
-use crate::{gui::GuiResources, input::InputScheme, nodes::network::Message, GameType};
+use crate::{
+    gui::{
+        controls::{self, ControlsUiState},
+        GuiResources,
+    },
+    input::InputScheme,
+    nodes::{
+        network::{Connection, LanSocket, Message, NetworkMode},
+        MatchRules,
+    },
+    GameType, LevelOrder, MAX_PLAYERS,
+};
 
-use std::net::UdpSocket;
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    rc::Rc,
+};
 
 const WINDOW_WIDTH: f32 = 700.;
 const WINDOW_HEIGHT: f32 = 400.;
 
-fn local_game_ui(ui: &mut ui::Ui, players: &mut Vec<InputScheme>) -> Option<GameType> {
+/// Everything about the match besides the fishes
+struct MatchUiState {
+    /// Index in MatchRules::PRESETS
+    rules: usize,
+    /// LevelOrder as usize
+    level_order: usize,
+}
+
+impl MatchUiState {
+    fn rules(&self) -> MatchRules {
+        MatchRules::PRESETS[self.rules].1
+    }
+
+    fn level_order(&self) -> LevelOrder {
+        match self.level_order {
+            0 => LevelOrder::InOrder,
+            1 => LevelOrder::Shuffled,
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn match_ui(ui: &mut ui::Ui, label_suffix: &str, state: &mut MatchUiState) {
+    let names = MatchRules::PRESETS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+
+    widgets::ComboBox::new(hash!(), &names)
+        .ratio(0.4)
+        .label(&format!("Rules{}", label_suffix))
+        .ui(ui, &mut state.rules);
+    widgets::ComboBox::new(hash!(), &["In order", "Shuffled"])
+        .ratio(0.4)
+        .label(&format!("Levels{}", label_suffix))
+        .ui(ui, &mut state.level_order);
+}
+
+fn local_game_ui(
+    ui: &mut ui::Ui,
+    players: &mut Vec<InputScheme>,
+    match_state: &mut MatchUiState,
+) -> Option<GameType> {
+    match_ui(ui, "", match_state);
+
     let gui_resources = storage::get_mut::<GuiResources>();
 
-    if players.len() < 2 {
+    if players.len() < MAX_PLAYERS {
         if is_key_pressed(KeyCode::V) {
             //
             if !players.contains(&InputScheme::KeyboardLeft) {
@@ -51,25 +109,22 @@ fn local_game_ui(ui: &mut ui::Ui, players: &mut Vec<InputScheme>) -> Option<Game
     ui.separator();
     ui.separator();
 
-    ui.group(hash!(), vec2(WINDOW_WIDTH / 2. - 50., 70.), |ui| {
-        if players.get(0).is_none() {
-            ui.label(None, "Player 1: Not connected");
-        }
-        if let Some(input) = players.get(0) {
-            ui.label(None, "Player 1: Connected!");
-            ui.label(None, &format!("{:?}", input));
-        }
-    });
-    ui.group(hash!(), vec2(WINDOW_WIDTH / 2. - 50., 70.), |ui| {
-        if players.get(1).is_none() {
-            ui.label(None, "Player 2: Not connected");
-        }
-        if let Some(input) = players.get(1) {
-            ui.label(None, "Player 2: Connected!");
-            ui.label(None, &format!("{:?}", input));
-        }
-    });
-    if players.len() == 2 {
+    for ix in 0..MAX_PLAYERS {
+        ui.group(
+            hash!("player", ix),
+            vec2(WINDOW_WIDTH / 2. - 50., 70.),
+            |ui| {
+                if players.get(ix).is_none() {
+                    ui.label(None, &format!("Player {}: Not connected", ix + 1));
+                }
+                if let Some(input) = players.get(ix) {
+                    ui.label(None, &format!("Player {}: Connected!", ix + 1));
+                    ui.label(None, &format!("{:?}", input));
+                }
+            },
+        );
+    }
+    if players.len() >= 2 {
         let btn_a = is_gamepad_btn_pressed(&*gui_resources, quad_gamepad::GamepadButton::A);
         let enter = is_key_pressed(KeyCode::Enter);
 
@@ -92,20 +147,31 @@ enum ConnectionKind {
 #[derive(Debug, PartialEq)]
 enum ConnectionStatus {
     Unknown,
+    /// Everybody answered, but with a different netcode, rules or levels order
+    SettingsMismatch,
     Connected,
 }
 
+#[derive(Debug, Clone, Copy)]
+struct PeerHandshake {
+    seed: u64,
+    players: usize,
+    settings: [u8; 3],
+}
+
 struct LanConnection {
     socket: UdpSocket,
     local_addr: String,
-    opponent_addr: String,
+    /// One address for each of the other peers
+    opponent_addrs: Vec<String>,
     status: ConnectionStatus,
+    seed: u64,
+    /// One for each of the other peers
+    opponent_handshakes: Vec<Option<PeerHandshake>>,
 }
 
 impl LanConnection {
     fn new() -> LanConnection {
-        use std::net::SocketAddr;
-
         let addrs = [
             SocketAddr::from(([0, 0, 0, 0], 3400)),
             SocketAddr::from(([0, 0, 0, 0], 3401)),
@@ -121,34 +187,119 @@ impl LanConnection {
         LanConnection {
             socket,
             local_addr,
-            opponent_addr: "".to_string(),
+            opponent_addrs: vec!["".to_string()],
             status: ConnectionStatus::Unknown,
+            seed: macroquad::miniquad::date::now().to_bits(),
+            opponent_handshakes: vec![None],
         }
     }
 
-    fn update(&mut self) {
-        let mut buf = [0; 100];
-        if self.socket.recv(&mut buf).is_ok() {
-            let _message: Message = nanoserde::DeBin::deserialize_bin(&buf[..]).ok().unwrap();
-            self.status = ConnectionStatus::Connected;
+    fn set_peers_count(&mut self, peers: usize) {
+        if self.opponent_addrs.len() != peers - 1 {
+            self.opponent_addrs.resize(peers - 1, "".to_string());
+            self.opponent_handshakes = vec![None; peers - 1];
+            self.status = ConnectionStatus::Unknown;
         }
     }
 
-    pub fn probe(&mut self) -> Option<()> {
-        self.socket.connect(&self.opponent_addr).ok()?;
+    fn opponent_socket_addrs(&self) -> Option<Vec<SocketAddr>> {
+        self.opponent_addrs
+            .iter()
+            .map(|addr| addr.to_socket_addrs().ok()?.next())
+            .collect()
+    }
 
-        for _ in 0..100 {
-            self.socket
-                .send(&nanoserde::SerBin::serialize_bin(&Message::Idle))
-                .ok()?;
+    /// "settings" are the local ones, see Message::Handshake
+    /// Peers with different settings would desync on the first round, no connection with them
+    fn update(&mut self, settings: [u8; 3]) {
+        let mut buf = [0; 100];
+        if let Ok((_, from)) = self.socket.recv_from(&mut buf) {
+            let message: Message = nanoserde::DeBin::deserialize_bin(&buf[..]).ok().unwrap();
+            if let Message::Handshake {
+                seed,
+                players,
+                settings,
+            } = message
+            {
+                let opponents = self.opponent_socket_addrs().unwrap_or_default();
+                if let Some(ix) = opponents.iter().position(|addr| *addr == from) {
+                    self.opponent_handshakes[ix] = Some(PeerHandshake {
+                        seed,
+                        players: players as usize,
+                        settings,
+                    });
+                }
+            }
+        }
+
+        if self.opponent_handshakes.iter().all(Option::is_some) {
+            let same = self
+                .opponent_handshakes
+                .iter()
+                .all(|handshake| handshake.unwrap().settings == settings);
+            self.status = if same {
+                ConnectionStatus::Connected
+            } else {
+                ConnectionStatus::SettingsMismatch
+            };
+        }
+    }
+
+    /// "players" is the amount of fishes played on this machine
+    pub fn probe(&mut self, players: usize, settings: [u8; 3]) -> Option<()> {
+        let handshake = nanoserde::SerBin::serialize_bin(&Message::Handshake {
+            seed: self.seed,
+            players: players as u8,
+            settings,
+        });
+
+        for addr in self.opponent_socket_addrs()? {
+            // an unreachable address should not stop probing the others
+            for _ in 0..100 {
+                let _ = self.socket.send_to(&handshake, addr);
+            }
         }
 
         None
     }
+
+    /// Peer ids are given out in the order of handshake seeds,
+    /// so all the peers agree on them without any extra messages
+    fn self_id(&self) -> usize {
+        self.opponent_handshakes
+            .iter()
+            .filter(|handshake| handshake.unwrap().seed > self.seed)
+            .count()
+    }
+
+    /// Amount of fishes on each peer, by peer id
+    fn peers_players(&self, players: usize) -> Vec<usize> {
+        let mut peers = self
+            .opponent_handshakes
+            .iter()
+            .map(|handshake| {
+                let handshake = handshake.unwrap();
+                (handshake.seed, handshake.players)
+            })
+            .collect::<Vec<_>>();
+        peers.push((self.seed, players));
+        peers.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        peers.into_iter().map(|(_, players)| players).collect()
+    }
+
+    fn match_seed(&self) -> u64 {
+        self.opponent_handshakes
+            .iter()
+            .fold(self.seed, |seed, handshake| seed ^ handshake.unwrap().seed)
+    }
 }
 
 struct NetworkUiState {
-    input_scheme: InputScheme,
+    /// One for each fish played on this machine
+    input_schemes: Vec<InputScheme>,
+    mode: NetworkMode,
+    peers: usize,
     connection_kind: ConnectionKind,
     lan_connection: Option<LanConnection>,
     #[cfg(feature = "steamworks")]
@@ -166,7 +317,11 @@ fn is_gamepad_btn_pressed(gui_resources: &GuiResources, btn: quad_gamepad::Gamep
     false
 }
 
-fn network_game_ui(ui: &mut ui::Ui, state: &mut NetworkUiState) -> Option<GameType> {
+fn network_game_ui(
+    ui: &mut ui::Ui,
+    state: &mut NetworkUiState,
+    match_state: &mut MatchUiState,
+) -> Option<GameType> {
     let mut connection_kind_ui = state.connection_kind as usize;
 
     #[cfg(not(feature = "steamworks"))]
@@ -190,6 +345,19 @@ fn network_game_ui(ui: &mut ui::Ui, state: &mut NetworkUiState) -> Option<GameTy
         _ => unreachable!(),
     }
 
+    let mut mode_ui = state.mode as usize;
+    widgets::ComboBox::new(hash!(), &["Delayed lockstep", "Rollback"])
+        .ratio(0.4)
+        .label("Netcode (same for all)")
+        .ui(ui, &mut mode_ui);
+    state.mode = match mode_ui {
+        0 => NetworkMode::DelayedLockstep,
+        1 => NetworkMode::Rollback,
+        _ => unreachable!(),
+    };
+
+    match_ui(ui, " (same for all)", match_state);
+
     if state.connection_kind == ConnectionKind::Lan {
         if state.lan_connection.is_none() {
             state.lan_connection = Some(LanConnection::new());
@@ -197,39 +365,67 @@ fn network_game_ui(ui: &mut ui::Ui, state: &mut NetworkUiState) -> Option<GameTy
         let connection = state.lan_connection.as_mut().unwrap();
         let mut self_addr = connection.local_addr.clone();
 
+        let mut peers_ui = state.peers - 2;
+        widgets::ComboBox::new(hash!(), &["2", "3", "4"])
+            .ratio(0.4)
+            .label("Peers (same for all)")
+            .ui(ui, &mut peers_ui);
+        state.peers = peers_ui + 2;
+        connection.set_peers_count(state.peers);
+
         widgets::InputText::new(hash!())
             .ratio(0.4)
             .label("Self addr")
             .ui(ui, &mut self_addr);
 
-        widgets::InputText::new(hash!())
-            .ratio(0.4)
-            .label("Opponent addr")
-            .ui(ui, &mut connection.opponent_addr);
+        for (ix, opponent_addr) in connection.opponent_addrs.iter_mut().enumerate() {
+            widgets::InputText::new(hash!("opponent addr", ix))
+                .ratio(0.4)
+                .label("Opponent addr")
+                .ui(ui, opponent_addr);
+        }
 
-        connection.update();
+        let settings = [
+            state.mode as u8,
+            match_state.rules as u8,
+            match_state.level_order as u8,
+        ];
+        connection.update(settings);
 
         if ui.button(None, "Probe connection") {
-            connection.probe();
+            connection.probe(state.input_schemes.len(), settings);
         }
 
         ui.label(
             None,
             &format!("LanConnection status: {:?}", connection.status),
         );
+        if connection.status == ConnectionStatus::SettingsMismatch {
+            ui.label(None, "Pick the same netcode, rules and levels, probe again");
+        }
 
-        if connection.status == ConnectionStatus::Connected
-            && ui.button(None, "Connect (A) (Enter)")
-        {
-            return Some(GameType::Network {
-                socket: Box::new(connection.socket.try_clone().unwrap()),
-                id: if connection.local_addr > connection.opponent_addr {
-                    0
-                } else {
-                    1
-                },
-                input_scheme: state.input_scheme,
-            });
+        if connection.status == ConnectionStatus::Connected {
+            let peers_players = connection.peers_players(state.input_schemes.len());
+            let players = peers_players.iter().sum::<usize>();
+
+            if state.input_schemes.is_empty() || players > MAX_PLAYERS {
+                ui.label(
+                    None,
+                    &format!("{} fishes, should be 2-{}", players, MAX_PLAYERS),
+                );
+            } else if ui.button(None, "Connect (A) (Enter)") {
+                return Some(GameType::Network {
+                    connection: Rc::new(Connection::new(Box::new(LanSocket {
+                        socket: connection.socket.try_clone().unwrap(),
+                        peers: connection.opponent_socket_addrs().unwrap(),
+                    }))),
+                    id: connection.self_id(),
+                    mode: state.mode,
+                    seed: connection.match_seed(),
+                    input_schemes: state.input_schemes.clone(),
+                    peers_players,
+                });
+            }
         }
     }
 
@@ -252,22 +448,30 @@ fn network_game_ui(ui: &mut ui::Ui, state: &mut NetworkUiState) -> Option<GameTy
                 ui.label(None, &format!("Status: {:?}", connection.status()));
 
                 if let fishsteam::SteamStatus::Ready = connection.status() {
-                    if ui.button(None, "Connect") {
+                    if !state.input_schemes.is_empty() && ui.button(None, "Connect") {
                         use crate::nodes::network::steam::SteamSocket;
 
                         let opponent_id = connection.opponent_id().unwrap();
                         return Some(GameType::Network {
-                            socket: Box::new(SteamSocket {
+                            connection: Rc::new(Connection::new(Box::new(SteamSocket {
                                 steam: connection.clone(),
                                 //networking: connection.client.networking(),
                                 opponent_id,
-                            }),
+                            }))),
                             id: if connection.self_id() > opponent_id {
                                 0
                             } else {
                                 1
                             },
-                            input_scheme: state.input_scheme,
+                            mode: state.mode,
+                            // no handshake over steam yet, but ids are
+                            // known to both peers. Netcode, rules and levels
+                            // are not checked, both should pick the same
+                            seed: connection.self_id().as_u64() ^ opponent_id.as_u64(),
+                            // no handshake - no way to tell the amount of
+                            // remote fishes, so one fish per peer on steam
+                            input_schemes: state.input_schemes[..1].to_vec(),
+                            peers_players: vec![1, 1],
                         });
                     }
                 }
@@ -275,40 +479,61 @@ fn network_game_ui(ui: &mut ui::Ui, state: &mut NetworkUiState) -> Option<GameTy
         }
     }
 
+    // re-probe after changing the inputs, remote peers should know
+    // about all the local fishes
     ui.label(
-        vec2(430., 310.),
-        &format!("Input: {:?}", state.input_scheme),
+        vec2(360., 290.),
+        &format!("Inputs: {:?}", state.input_schemes),
     );
-    ui.label(vec2(360., 330.), "Press V/L/Start to change");
+    ui.label(vec2(360., 310.), "Press V/L/Start to add a fish");
+    if ui.button(vec2(360., 330.), "Clear inputs") {
+        state.input_schemes.clear();
+    }
+    let mut pressed = vec![];
     if is_key_pressed(KeyCode::V) {
-        state.input_scheme = InputScheme::KeyboardLeft;
+        pressed.push(InputScheme::KeyboardLeft);
     }
     if is_key_pressed(KeyCode::L) {
-        state.input_scheme = InputScheme::KeyboardRight;
+        pressed.push(InputScheme::KeyboardRight);
     }
     for ix in 0..quad_gamepad::MAX_DEVICES {
         let gui_resources = storage::get_mut::<GuiResources>();
         let gamepad_state = gui_resources.gamepads.state(ix);
 
         if gamepad_state.digital_state[quad_gamepad::GamepadButton::Start as usize] {
-            state.input_scheme = InputScheme::Gamepad(ix);
+            pressed.push(InputScheme::Gamepad(ix));
+        }
+    }
+    for input_scheme in pressed {
+        if !state.input_schemes.contains(&input_scheme)
+            && state.input_schemes.len() < MAX_PLAYERS - 1
+        {
+            state.input_schemes.push(input_scheme);
         }
     }
 
     None
 }
 
-pub async fn game_type() -> GameType {
+pub async fn game_type() -> (GameType, MatchRules, LevelOrder) {
     let mut players = vec![];
+    let mut match_state = MatchUiState {
+        rules: 0,
+        level_order: LevelOrder::InOrder as usize,
+    };
 
     let mut network_ui_state = NetworkUiState {
         lan_connection: None,
         #[cfg(feature = "steamworks")]
         steam_connection: None,
-        input_scheme: InputScheme::KeyboardLeft,
+        input_schemes: vec![InputScheme::KeyboardLeft],
+        mode: NetworkMode::DelayedLockstep,
+        peers: 2,
         connection_kind: ConnectionKind::Lan,
     };
 
+    let mut controls_state = ControlsUiState::new();
+
     let mut tab = 0;
     loop {
         let mut res = None;
@@ -317,21 +542,25 @@ pub async fn game_type() -> GameType {
             let mut gui_resources = storage::get_mut::<GuiResources>();
 
             gui_resources.gamepads.update();
+        }
+
+        // while rebinding, arrows and bumpers are the new controls
+        if !controls_state.is_waiting() {
+            let gui_resources = storage::get::<GuiResources>();
 
             if is_key_pressed(KeyCode::Left)
                 || is_gamepad_btn_pressed(&*gui_resources, quad_gamepad::GamepadButton::BumperLeft)
                 || is_gamepad_btn_pressed(&*gui_resources, quad_gamepad::GamepadButton::ThumbLeft)
             {
-                tab += 1;
-                tab %= 2;
+                tab += 2;
+                tab %= 3;
             }
-            // for two tabs going left and right is the same thing
             if is_key_pressed(KeyCode::Right)
                 || is_gamepad_btn_pressed(&*gui_resources, quad_gamepad::GamepadButton::BumperRight)
                 || is_gamepad_btn_pressed(&*gui_resources, quad_gamepad::GamepadButton::ThumbRight)
             {
                 tab += 1;
-                tab %= 2;
+                tab %= 3;
             }
         }
 
@@ -350,16 +579,19 @@ pub async fn game_type() -> GameType {
             |ui| match widgets::Tabbar::new(
                 hash!(),
                 vec2(WINDOW_WIDTH - 50., 50.),
-                &["<< Local game, LT", "Network game, RT >>"],
+                &["<< Local game, LT", "Network game", "Controls, RT >>"],
             )
             .selected_tab(Some(&mut tab))
             .ui(ui)
             {
                 0 => {
-                    res = local_game_ui(ui, &mut players);
+                    res = local_game_ui(ui, &mut players, &mut match_state);
                 }
                 1 => {
-                    res = network_game_ui(ui, &mut network_ui_state);
+                    res = network_game_ui(ui, &mut network_ui_state, &mut match_state);
+                }
+                2 => {
+                    controls::controls_ui(ui, &mut controls_state);
                 }
                 _ => unreachable!(),
             },
@@ -368,7 +600,7 @@ pub async fn game_type() -> GameType {
         root_ui().pop_skin();
 
         if let Some(res) = res {
-            return res;
+            return (res, match_state.rules(), match_state.level_order());
         }
         next_frame().await;
     }
@@ -6,15 +6,12 @@ use macroquad::{
 
 use crate::gui::GuiResources;
 
-// pause menu is temporary not used by the battle, but will be back soon
-#[allow(dead_code)]
 pub enum PauseResult {
     Quit,
     Close,
     Nothing,
 }
 
-#[allow(dead_code)]
 pub fn gui() -> PauseResult {
     let gui_resources = storage::get::<GuiResources>();
 
@@ -40,3 +37,29 @@ pub fn gui() -> PauseResult {
 
     res
 }
+
+/// Shown while some of the gamepads are unplugged
+/// "players" are the fish numbers, starting from 1
+pub fn disconnected_gui(players: &[usize]) {
+    let gui_resources = storage::get::<GuiResources>();
+
+    let height = 80. + 20. * players.len() as f32;
+    root_ui().push_skin(&gui_resources.skins.login_skin);
+    widgets::Window::new(
+        hash!(),
+        vec2(
+            screen_width() / 2. - 170.,
+            screen_height() / 2. - height / 2.,
+        ),
+        vec2(340., height),
+    )
+    .titlebar(false)
+    .ui(&mut root_ui(), |ui| {
+        for player in players {
+            ui.label(None, &format!("Player {} controller disconnected", player));
+        }
+        ui.label(None, "Plug it back in, or press Start");
+        ui.label(None, "on another gamepad to take its place");
+    });
+    root_ui().pop_skin();
+}
@@ -0,0 +1,247 @@
+//! Running the simulation with nothing to show
+//!
+//! Only the map and the collision world are loaded - no textures, sounds or particles,
+//! see Resources::headless, and the simulation is advanced in a tight loop, as fast
+//! as it goes, with scripted inputs.
+//!
+//! Running with no window at all is not supported. macroquad's scene reads its
+//! clock from the window context the moment it is created, and "scene::clear" goes
+//! through the context too, so there is no way to have any nodes without a window.
+//! No GPU is needed though: nothing is drawn, any display with any GL will do.
+//! On a machine without one (like CI) run the checks under "xvfb-run", mesa's
+//! software renderer is enough.
+//!
+//! "cargo test" runs a few scripted situations, each in its own window, and
+//! checks they end up the way they should.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    items,
+    nodes::{Camera, Player},
+    simulation, Input, Resources,
+};
+
+pub struct Headless {
+    pub players: Vec<Handle<Player>>,
+}
+
+impl Headless {
+    pub async fn new(map: &str, players: usize, seed: u64) -> Headless {
+        let resources = Resources::headless(map).await.unwrap();
+        let bounds = {
+            let map = &resources.tiled_map.raw_tiled_map;
+
+            Rect::new(
+                0.,
+                0.,
+                (map.tilewidth * map.width) as f32,
+                (map.tileheight * map.height) as f32,
+            )
+        };
+        storage::store(resources);
+
+        simulation::srand(seed);
+
+        let players = (0..players)
+            .map(|id| scene::add_node(Player::new(id as u8, id as i32)))
+            .collect();
+        // nothing is drawn, but some items shake the camera
+        scene::add_node(Camera::new(bounds));
+
+        Headless { players }
+    }
+
+    /// Item by its "tiled_name"
+    pub fn spawn_item(&self, name: &str, pos: Vec2) -> HandleUntyped {
+        items::spawn(name, pos)
+    }
+
+    /// Let all the spawned nodes get "ready" and provide their capabilities
+    /// Should be called after spawning everything, before the first "step"
+    pub async fn ready(&self) {
+        next_frame().await;
+    }
+
+    pub fn player(&self, id: usize) -> RefMut<Player> {
+        scene::get_node(self.players[id])
+    }
+
+    /// One simulation frame, one input for each player
+    /// Only the levels are used, edges are computed here, the same way collect_input does
+    pub fn step(&self, inputs: &[Input]) {
+        for (player, input) in self.players.iter().zip(inputs) {
+            let mut player = scene::get_node(*player);
+            let input = input.with_edges(&player.input);
+            player.apply_input(input);
+        }
+
+        simulation::advance();
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        scene::clear();
+    }
+}
+
+/// On the lev01 bottom floor, fishes are standing on y = 522
+pub const PLAYER1_POS: Vec2 = const_vec2!([200., 480.]);
+pub const FLOOR_Y: f32 = 576.;
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, sync::Mutex};
+
+    use super::*;
+
+    const MAP: &str = "assets/levels/lev01.json";
+
+    const PLAYER2_POS: Vec2 = const_vec2!([600., 480.]);
+
+    /// macroquad's context is a global, so one window at a time
+    static WINDOW: Mutex<()> = Mutex::new(());
+
+    /// Opens a window, places the fishes on the bottom floor, runs "setup" and
+    /// simulates up to "frames" frames with "input" for both fishes.
+    /// True if "happened" was true on any of the frames
+    fn scenario(
+        setup: fn(&Headless),
+        input: fn(frame: usize) -> [Input; 2],
+        frames: usize,
+        happened: fn(&Headless) -> bool,
+    ) -> bool {
+        // without a display miniquad exits the whole process with 0,
+        // and the tests would look like they passed
+        #[cfg(target_os = "linux")]
+        assert!(
+            std::env::var_os("DISPLAY").is_some(),
+            "No display, run under xvfb-run"
+        );
+
+        let _window = WINDOW.lock().unwrap_or_else(|err| err.into_inner());
+
+        let result = Rc::new(Cell::new(false));
+        macroquad::Window::from_config(crate::window_conf(), {
+            let result = result.clone();
+            async move {
+                items::load_items().await;
+
+                let headless = Headless::new(MAP, 2, 0).await;
+                headless.player(0).body.teleport(PLAYER1_POS);
+                headless.player(1).body.teleport(PLAYER2_POS);
+                setup(&headless);
+                headless.ready().await;
+
+                for frame in 0..frames {
+                    headless.step(&input(frame));
+
+                    if happened(&headless) {
+                        result.set(true);
+                        break;
+                    }
+                }
+            }
+        });
+
+        result.get()
+    }
+
+    fn no_setup(_: &Headless) {}
+
+    fn with_sword(headless: &Headless) {
+        headless.spawn_item("sword", PLAYER1_POS);
+    }
+
+    #[test]
+    fn walk() {
+        assert!(scenario(
+            no_setup,
+            |frame| {
+                let input = Input {
+                    right: frame >= 30,
+                    ..Default::default()
+                };
+                [input, Input::default()]
+            },
+            120,
+            |headless| headless.player(0).body.pos.x > PLAYER1_POS.x + 100.,
+        ));
+    }
+
+    #[test]
+    fn jump() {
+        assert!(scenario(
+            no_setup,
+            |frame| {
+                let input = Input {
+                    jump: frame == 40,
+                    ..Default::default()
+                };
+                [input, Input::default()]
+            },
+            80,
+            |headless| headless.player(0).body.pos.y < PLAYER1_POS.y - 20.,
+        ));
+    }
+
+    #[test]
+    fn pickup() {
+        assert!(scenario(
+            with_sword,
+            |frame| {
+                let input = Input {
+                    throw: frame == 40,
+                    ..Default::default()
+                };
+                [input, Input::default()]
+            },
+            60,
+            |headless| headless.player(0).weapon.is_some(),
+        ));
+    }
+
+    #[test]
+    fn sword_kill() {
+        assert!(scenario(
+            |headless| {
+                with_sword(headless);
+                headless
+                    .player(1)
+                    .body
+                    .teleport(PLAYER1_POS + vec2(50., 0.));
+            },
+            |frame| {
+                let input = Input {
+                    throw: frame == 40,
+                    right: frame == 45,
+                    fire: frame == 50,
+                    ..Default::default()
+                };
+                [input, Input::default()]
+            },
+            90,
+            |headless| headless.player(1).state() == Player::ST_DEATH,
+        ));
+    }
+
+    #[test]
+    fn sproinger() {
+        assert!(scenario(
+            |headless| {
+                // sproinger sprite is 20 pixels high, trigger is at its bottom
+                headless.spawn_item("sproinger", vec2(PLAYER1_POS.x - 5., FLOOR_Y - 20.));
+            },
+            |_| [Input::default(), Input::default()],
+            60,
+            |headless| headless.player(0).body.pos.y < PLAYER1_POS.y - 100.,
+        ));
+    }
+}
@@ -1,10 +1,15 @@
 use macroquad::{
-    experimental::collections::storage,
-    input::{is_key_down, KeyCode},
+    experimental::{collections::storage, scene},
+    input::{is_key_down, mouse_position},
+    math::{vec2, Vec2},
 };
 
 use nanoserde::{DeBin, SerBin};
 
+mod bindings;
+
+pub use bindings::{AimBinding, Binding, Bindings, ACTIONS, BUTTONS, CONFIG_PATH};
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InputScheme {
     /// Left side of the keyboard, around WASD
@@ -15,8 +20,9 @@ pub enum InputScheme {
     Gamepad(usize),
 }
 
-#[derive(Default, Debug, Clone, Copy, DeBin, SerBin)]
-pub struct Input {
+/// Actions that went down or up since the previous input
+#[derive(Default, Debug, Clone, Copy, PartialEq, DeBin, SerBin)]
+pub struct InputEdges {
     pub jump: bool,
     pub throw: bool,
     pub fire: bool,
@@ -27,50 +33,222 @@ pub struct Input {
     pub down: bool,
 }
 
-pub fn collect_input(scheme: InputScheme) -> Input {
-    let mut input = Input::default();
+/// Aim direction, quantized to keep the simulation deterministic:
+/// the same bytes go over the network and give the same Vec2 on every peer
+#[derive(Debug, Clone, Copy, PartialEq, DeBin, SerBin)]
+pub struct Aim {
+    x: i16,
+    y: i16,
+}
 
-    if let InputScheme::KeyboardLeft = scheme {
-        input.throw = is_key_down(KeyCode::C);
-        input.fire = is_key_down(KeyCode::V) || is_key_down(KeyCode::LeftControl);
+impl Aim {
+    const STEPS: f32 = 100.;
 
-        input.jump = is_key_down(KeyCode::W) || is_key_down(KeyCode::Space);
-        input.left = is_key_down(KeyCode::A);
-        input.down = is_key_down(KeyCode::S);
-        input.right = is_key_down(KeyCode::D);
+    /// None if the vector is too short to tell the direction
+    pub fn quantize(vector: Vec2) -> Option<Aim> {
+        if vector.length() < f32::EPSILON {
+            return None;
+        }
 
-        input.slide = is_key_down(KeyCode::C);
+        let vector = vector.normalize() * Self::STEPS;
+        Some(Aim {
+            x: vector.x.round() as i16,
+            y: vector.y.round() as i16,
+        })
     }
 
-    if let InputScheme::KeyboardRight = scheme {
-        input.throw = is_key_down(KeyCode::K);
-        input.fire = is_key_down(KeyCode::L);
+    /// Normalized
+    pub fn direction(&self) -> Vec2 {
+        vec2(self.x as f32, self.y as f32).normalize()
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, DeBin, SerBin)]
+pub struct Input {
+    pub jump: bool,
+    pub throw: bool,
+    pub fire: bool,
+    pub slide: bool,
+
+    pub left: bool,
+    pub right: bool,
+    pub down: bool,
+
+    /// Edges are computed once, when the input is collected, and travel
+    /// over the network together with the levels.
+    /// So a press is never lost, no matter how many frames are simulated at once.
+    pub pressed: InputEdges,
+    pub released: InputEdges,
 
-        input.jump = is_key_down(KeyCode::Up);
-        input.left = is_key_down(KeyCode::Left);
-        input.down = is_key_down(KeyCode::Down);
-        input.right = is_key_down(KeyCode::Right);
+    /// Only for the schemes with an aim binding, used by the weapons that
+    /// can aim, see capabilities::Weapon::aim
+    pub aim: Option<Aim>,
+
+    /// The fish controller is unplugged. A network game skips the frames
+    /// with any of those, so all the peers pause on the same frame
+    pub unplugged: bool,
+}
 
-        input.slide = is_key_down(KeyCode::RightControl);
+impl Input {
+    /// Same input, with the edges against the previously collected one
+    pub fn with_edges(mut self, previous: &Input) -> Input {
+        let edge = |now: bool, before: bool| now && !before;
+
+        self.pressed = InputEdges {
+            jump: edge(self.jump, previous.jump),
+            throw: edge(self.throw, previous.throw),
+            fire: edge(self.fire, previous.fire),
+            slide: edge(self.slide, previous.slide),
+            left: edge(self.left, previous.left),
+            right: edge(self.right, previous.right),
+            down: edge(self.down, previous.down),
+        };
+        self.released = InputEdges {
+            jump: edge(previous.jump, self.jump),
+            throw: edge(previous.throw, self.throw),
+            fire: edge(previous.fire, self.fire),
+            slide: edge(previous.slide, self.slide),
+            left: edge(previous.left, self.left),
+            right: edge(previous.right, self.right),
+            down: edge(previous.down, self.down),
+        };
+
+        self
     }
 
-    if let InputScheme::Gamepad(ix) = scheme {
-        use quad_gamepad::GamepadButton::*;
+    /// Input field by its name in bindings::ACTIONS
+    fn action_mut(&mut self, action: &str) -> &mut bool {
+        match action {
+            "jump" => &mut self.jump,
+            "throw" => &mut self.throw,
+            "fire" => &mut self.fire,
+            "slide" => &mut self.slide,
+            "left" => &mut self.left,
+            "right" => &mut self.right,
+            "down" => &mut self.down,
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn is_gamepad_connected(ix: usize) -> bool {
+    let gui_resources = storage::get::<crate::gui::GuiResources>();
+    gui_resources.gamepads.state(ix).status == quad_gamepad::ControllerStatus::Connected
+}
+
+/// Indices in "schemes" of the gamepads that are gone
+/// quad_gamepad keeps the last state of a disconnected pad,
+/// so the fish would just stop, or keep running
+fn disconnected_gamepads(schemes: &[InputScheme]) -> Vec<usize> {
+    schemes
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, scheme)| match scheme {
+            InputScheme::Gamepad(gamepad) if !is_gamepad_connected(*gamepad) => Some(ix),
+            _ => None,
+        })
+        .collect()
+}
 
-        let gui_resources = storage::get_mut::<crate::gui::GuiResources>();
+/// Connected gamepad, not used by any of "schemes", with Start just pressed
+/// Same as joining in the main menu
+fn free_gamepad_start_pressed(schemes: &[InputScheme]) -> Option<usize> {
+    let gui_resources = storage::get::<crate::gui::GuiResources>();
+    let start = quad_gamepad::GamepadButton::Start as usize;
 
-        let state = gui_resources.gamepads.state(ix);
+    (0..quad_gamepad::MAX_DEVICES).find(|ix| {
+        let state = gui_resources.gamepads.state(*ix);
 
-        input.throw = state.digital_state[X as usize];
-        input.fire = state.digital_state[B as usize];
+        state.status == quad_gamepad::ControllerStatus::Connected
+            && state.digital_state[start]
+            && !state.digital_state_prev[start]
+            && !schemes.contains(&InputScheme::Gamepad(*ix))
+    })
+}
 
-        input.jump = state.digital_state[A as usize];
-        input.left = state.analog_state[0] < -0.5;
-        input.right = state.analog_state[0] > 0.5;
-        input.down = state.analog_state[1] > 0.5;
+/// Gives the first of the disconnected schemes to a free gamepad, if someone
+/// pressed Start on one. Returns the indices of the schemes still disconnected
+pub fn reclaim_gamepads(schemes: &mut [InputScheme]) -> Vec<usize> {
+    if let Some(&first) = disconnected_gamepads(schemes).first() {
+        if let Some(gamepad) = free_gamepad_start_pressed(schemes) {
+            schemes[first] = InputScheme::Gamepad(gamepad);
+        }
+    }
+
+    disconnected_gamepads(schemes)
+}
 
-        input.slide = state.digital_state[Y as usize];
+/// Is the binding held right now, for the gamepad "ix"
+pub fn is_binding_down(binding: Binding, gamepad: Option<usize>, deadzone: f32) -> bool {
+    match (binding, gamepad) {
+        (Binding::Key(key), _) => is_key_down(key),
+        (Binding::Button(button), Some(ix)) => {
+            let gui_resources = storage::get::<crate::gui::GuiResources>();
+            gui_resources.gamepads.state(ix).digital_state[button as usize]
+        }
+        (Binding::Axis(axis, positive), Some(ix)) => {
+            let gui_resources = storage::get::<crate::gui::GuiResources>();
+            let value = gui_resources.gamepads.state(ix).analog_state[axis];
+            if positive {
+                value > deadzone
+            } else {
+                value < -deadzone
+            }
+        }
+        // gamepad bindings on a keyboard scheme do nothing
+        (_, None) => false,
     }
+}
+
+fn collect_aim(
+    binding: AimBinding,
+    gamepad: Option<usize>,
+    origin: Vec2,
+    deadzone: f32,
+) -> Option<Aim> {
+    match (binding, gamepad) {
+        (AimBinding::Mouse, _) => {
+            let camera = scene::find_node_by_type::<crate::nodes::Camera>()?;
+            let mouse = camera.screen_to_world(mouse_position().into());
+
+            Aim::quantize(mouse - origin)
+        }
+        (AimBinding::Stick(axis), Some(ix)) => {
+            let gui_resources = storage::get::<crate::gui::GuiResources>();
+            let state = gui_resources.gamepads.state(ix);
+            let stick = vec2(state.analog_state[axis], state.analog_state[axis + 1]);
+
+            // released stick - back to facing forward
+            if stick.length() < deadzone {
+                return None;
+            }
+            Aim::quantize(stick)
+        }
+        (AimBinding::Stick(_), None) => None,
+    }
+}
+
+/// "previous" is the last input of the same scheme that made it into the simulation
+/// "origin" is the fish center in the world, the mouse aims from there
+pub fn collect_input(scheme: InputScheme, previous: &Input, origin: Vec2) -> Input {
+    let mut input = Input::default();
+
+    let gamepad = match scheme {
+        InputScheme::Gamepad(ix) => Some(ix),
+        _ => None,
+    };
+
+    let bindings = storage::get::<Bindings>();
+    let scheme = bindings.scheme(scheme);
+    for (action, bindings) in ACTIONS.iter().zip(scheme.actions.iter()) {
+        *input.action_mut(action) = bindings
+            .iter()
+            .any(|binding| is_binding_down(*binding, gamepad, scheme.deadzone));
+    }
+
+    input.aim = scheme
+        .aim
+        .and_then(|aim| collect_aim(aim, gamepad, origin, scheme.deadzone));
 
-    input
+    input.with_edges(previous)
 }
@@ -0,0 +1,473 @@
+//! Which keys, buttons and sticks are the controls, per input scheme
+//! Loaded from config/input.toml, the default one is what used to be hardcoded.
+
+use macroquad::{file::load_string, input::KeyCode};
+use quad_gamepad::GamepadButton;
+
+use super::InputScheme;
+
+pub const CONFIG_PATH: &str = "config/input.toml";
+
+/// "Input" fields, in the same order as in the config
+pub const ACTIONS: [&str; 7] = ["jump", "throw", "fire", "slide", "left", "right", "down"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(GamepadButton),
+    /// Stick axis index and direction, true for positive
+    Axis(usize, bool),
+}
+
+impl Binding {
+    /// "key:W", "button:A", "axis:0-" or "axis:1+"
+    /// Key and button names are the same as in KeyCode and GamepadButton
+    pub fn parse(name: &str) -> Result<Binding, String> {
+        let (kind, value) = name
+            .split_once(':')
+            .ok_or_else(|| format!("\"{}\": no \"key:\", \"button:\" or \"axis:\"", name))?;
+        let unknown = || format!("\"{}\": unknown {}", name, kind);
+
+        match kind {
+            "key" => KEYS
+                .iter()
+                .find(|key| format!("{:?}", key) == value)
+                .map(|key| Binding::Key(*key))
+                .ok_or_else(unknown),
+            "button" => BUTTONS
+                .iter()
+                .find(|button| format!("{:?}", button) == value)
+                .map(|button| Binding::Button(*button))
+                .ok_or_else(unknown),
+            "axis" => {
+                let positive = value.ends_with('+');
+                if !positive && !value.ends_with('-') {
+                    return Err(unknown());
+                }
+                match value[..value.len() - 1].parse::<usize>() {
+                    Ok(axis) if axis < quad_gamepad::MAX_ANALOG => {
+                        Ok(Binding::Axis(axis, positive))
+                    }
+                    _ => Err(unknown()),
+                }
+            }
+            _ => Err(unknown()),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("key:{:?}", key),
+            Binding::Button(button) => format!("button:{:?}", button),
+            Binding::Axis(axis, positive) => {
+                format!("axis:{}{}", axis, if *positive { '+' } else { '-' })
+            }
+        }
+    }
+}
+
+/// Where the aim vector comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AimBinding {
+    /// From the fish to the mouse cursor
+    Mouse,
+    /// Stick with the given X axis index, Y is the next one
+    Stick(usize),
+}
+
+impl AimBinding {
+    /// "mouse" or "stick:2"
+    pub fn parse(name: &str) -> Result<AimBinding, String> {
+        if name == "mouse" {
+            return Ok(AimBinding::Mouse);
+        }
+
+        match name
+            .strip_prefix("stick:")
+            .map(|axis| axis.parse::<usize>())
+        {
+            Some(Ok(axis)) if axis + 1 < quad_gamepad::MAX_ANALOG => Ok(AimBinding::Stick(axis)),
+            _ => Err(format!("\"{}\": not \"mouse\" or \"stick:<index>\"", name)),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            AimBinding::Mouse => "mouse".to_string(),
+            AimBinding::Stick(axis) => format!("stick:{}", axis),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemeBindings {
+    /// Bindings for each of ACTIONS, any of them triggers the action
+    pub actions: [Vec<Binding>; ACTIONS.len()],
+    /// No aim - weapons shoot where the fish is facing
+    pub aim: Option<AimBinding>,
+    /// How far a stick should be moved to count as pressed
+    pub deadzone: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    pub keyboard_left: SchemeBindings,
+    pub keyboard_right: SchemeBindings,
+    /// Same for all the gamepads
+    pub gamepad: SchemeBindings,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings::parse(DEFAULT_CONFIG).unwrap()
+    }
+}
+
+impl Bindings {
+    /// config/input.toml, or the defaults if there is no config
+    /// A broken config is reported and the defaults are used instead
+    pub async fn load() -> Bindings {
+        let config = match load_string(CONFIG_PATH).await {
+            Ok(config) => config,
+            Err(_) => return Bindings::default(),
+        };
+
+        Bindings::parse(&config).unwrap_or_else(|err| {
+            println!("{}: {}, using the default controls", CONFIG_PATH, err);
+            Bindings::default()
+        })
+    }
+
+    pub fn parse(config: &str) -> Result<Bindings, String> {
+        let toml = nanoserde::TomlParser::parse(config).map_err(|err| format!("{:?}", err))?;
+
+        let scheme = |name: &str| -> Result<SchemeBindings, String> {
+            let mut actions: [Vec<Binding>; ACTIONS.len()] = Default::default();
+
+            for (action, bindings) in ACTIONS.iter().zip(actions.iter_mut()) {
+                let key = format!("{}.{}", name, action);
+                match toml.get(&key) {
+                    Some(nanoserde::Toml::SimpleArray(names)) => {
+                        for binding in names {
+                            match binding {
+                                nanoserde::Toml::Str(binding) => {
+                                    bindings.push(Binding::parse(binding)?)
+                                }
+                                _ => return Err(format!("{}: not a string", key)),
+                            }
+                        }
+                    }
+                    // unbound actions are fine, just useless
+                    None => {}
+                    _ => return Err(format!("{}: not a list", key)),
+                }
+            }
+
+            let aim = match toml.get(&format!("{}.aim", name)) {
+                Some(nanoserde::Toml::Str(aim)) => Some(AimBinding::parse(aim)?),
+                None => None,
+                _ => return Err(format!("{}.aim: not a string", name)),
+            };
+
+            let deadzone = match toml.get(&format!("{}.deadzone", name)) {
+                Some(nanoserde::Toml::Num(deadzone)) => *deadzone as f32,
+                _ => 0.5,
+            };
+
+            Ok(SchemeBindings {
+                actions,
+                aim,
+                deadzone,
+            })
+        };
+
+        let bindings = Bindings {
+            keyboard_left: scheme("keyboard_left")?,
+            keyboard_right: scheme("keyboard_right")?,
+            gamepad: scheme("gamepad")?,
+        };
+
+        let conflicts = bindings.conflicts();
+        if !conflicts.is_empty() {
+            return Err(conflicts.join(", "));
+        }
+
+        Ok(bindings)
+    }
+
+    pub fn scheme(&self, scheme: InputScheme) -> &SchemeBindings {
+        match scheme {
+            InputScheme::KeyboardLeft => &self.keyboard_left,
+            InputScheme::KeyboardRight => &self.keyboard_right,
+            InputScheme::Gamepad(_) => &self.gamepad,
+        }
+    }
+
+    pub fn scheme_mut(&mut self, scheme: InputScheme) -> &mut SchemeBindings {
+        match scheme {
+            InputScheme::KeyboardLeft => &mut self.keyboard_left,
+            InputScheme::KeyboardRight => &mut self.keyboard_right,
+            InputScheme::Gamepad(_) => &mut self.gamepad,
+        }
+    }
+
+    /// Bindings used twice: for two actions of the same scheme, or by both
+    /// keyboard schemes - two fishes on the same keyboard can't share keys
+    /// Gamepads are all different devices, sharing is fine for them
+    /// Also the ones taken by the game itself, see RESERVED
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut used: Vec<(Binding, &str, &str)> = vec![];
+        let mut conflicts = vec![];
+
+        let schemes = [
+            ("keyboard_left", &self.keyboard_left),
+            ("keyboard_right", &self.keyboard_right),
+            ("gamepad", &self.gamepad),
+        ];
+        for (scheme, bindings) in schemes.iter() {
+            for (action, bindings) in ACTIONS.iter().zip(bindings.actions.iter()) {
+                for binding in bindings {
+                    if let Some((_, usage)) = RESERVED.iter().find(|(other, _)| other == binding) {
+                        conflicts.push(format!(
+                            "{} is both {}.{} and {}",
+                            binding.name(),
+                            scheme,
+                            action,
+                            usage
+                        ));
+                    }
+
+                    let same = used.iter().find(|(other, other_scheme, _)| {
+                        other == binding
+                            && (other_scheme == scheme || matches!(binding, Binding::Key(_)))
+                    });
+                    if let Some((_, other_scheme, other_action)) = same {
+                        conflicts.push(format!(
+                            "{} is both {}.{} and {}.{}",
+                            binding.name(),
+                            other_scheme,
+                            other_action,
+                            scheme,
+                            action
+                        ));
+                    }
+                    used.push((*binding, scheme, action));
+                }
+            }
+        }
+
+        // there is only one mouse
+        if self.keyboard_left.aim == Some(AimBinding::Mouse)
+            && self.keyboard_right.aim == Some(AimBinding::Mouse)
+        {
+            conflicts.push("mouse is both keyboard_left.aim and keyboard_right.aim".to_string());
+        }
+
+        conflicts
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::from(CONFIG_HEADER);
+
+        let schemes = [
+            ("keyboard_left", &self.keyboard_left),
+            ("keyboard_right", &self.keyboard_right),
+            ("gamepad", &self.gamepad),
+        ];
+        for (scheme, bindings) in schemes.iter() {
+            toml += &format!("\n[{}]\ndeadzone = {:?}\n", scheme, bindings.deadzone);
+            if let Some(aim) = bindings.aim {
+                toml += &format!("aim = \"{}\"\n", aim.name());
+            }
+            for (action, bindings) in ACTIONS.iter().zip(bindings.actions.iter()) {
+                let names = bindings
+                    .iter()
+                    .map(|binding| format!("\"{}\"", binding.name()))
+                    .collect::<Vec<_>>();
+                toml += &format!("{} = [{}]\n", action, names.join(", "));
+            }
+        }
+
+        toml
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all("config")?;
+        std::fs::write(CONFIG_PATH, self.to_toml())
+    }
+}
+
+const CONFIG_HEADER: &str = r#"# Controls, see src/input/bindings.rs
+# "key:<KeyCode>", "button:<GamepadButton>" or "axis:<index><+ or ->"
+# Everything in the list triggers the action, a binding can't be used twice
+# on the same scheme, keys can't be shared between the keyboard schemes
+# Optional "aim" is "mouse" or "stick:<index of the X axis>", without it
+# weapons shoot where the fish is facing. Axis indices depend on the platform
+"#;
+
+/// Bindings the game reacts to during a match, whatever the controls are
+/// V, L and arrows only do something in the menus, it's fine to play with them
+const RESERVED: &[(Binding, &str)] = &[
+    (Binding::Key(KeyCode::Escape), "pause"),
+    (Binding::Key(KeyCode::Enter), "confirm in the menus"),
+    (Binding::Button(GamepadButton::Start), "join"),
+    // debug keys, see LocalNetwork and Player
+    (Binding::Key(KeyCode::Z), "debug pause"),
+    (Binding::Key(KeyCode::X), "debug unpause"),
+    (Binding::Key(KeyCode::Y), "debug self destruct"),
+    (Binding::Key(KeyCode::U), "debug self destruct"),
+    (Binding::Key(KeyCode::Q), "debug camera shake"),
+    (Binding::Key(KeyCode::Key0), "debug AI toggle"),
+    (Binding::Key(KeyCode::Key1), "debug AI toggle"),
+];
+
+/// Used when there is no config/input.toml, or it is broken
+const DEFAULT_CONFIG: &str = include_str!("../../config/input.toml");
+
+pub const BUTTONS: &[GamepadButton] = {
+    use GamepadButton::*;
+    &[
+        A,
+        B,
+        X,
+        Y,
+        DpadUp,
+        DpadDown,
+        DpadRight,
+        DpadLeft,
+        BumperLeft,
+        BumperRight,
+        ThumbLeft,
+        ThumbRight,
+        Select,
+        Start,
+        Back,
+    ]
+};
+
+/// All the KeyCodes, to find one by name
+const KEYS: &[KeyCode] = {
+    use KeyCode::*;
+    &[
+        Space,
+        Apostrophe,
+        Comma,
+        Minus,
+        Period,
+        Slash,
+        Key0,
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Semicolon,
+        Equal,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        LeftBracket,
+        Backslash,
+        RightBracket,
+        GraveAccent,
+        World1,
+        World2,
+        Escape,
+        Enter,
+        Tab,
+        Backspace,
+        Insert,
+        Delete,
+        Right,
+        Left,
+        Down,
+        Up,
+        PageUp,
+        PageDown,
+        Home,
+        End,
+        CapsLock,
+        ScrollLock,
+        NumLock,
+        PrintScreen,
+        Pause,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        F13,
+        F14,
+        F15,
+        F16,
+        F17,
+        F18,
+        F19,
+        F20,
+        F21,
+        F22,
+        F23,
+        F24,
+        F25,
+        Kp0,
+        Kp1,
+        Kp2,
+        Kp3,
+        Kp4,
+        Kp5,
+        Kp6,
+        Kp7,
+        Kp8,
+        Kp9,
+        KpDecimal,
+        KpDivide,
+        KpMultiply,
+        KpSubtract,
+        KpAdd,
+        KpEnter,
+        KpEqual,
+        LeftShift,
+        LeftControl,
+        LeftAlt,
+        LeftSuper,
+        RightShift,
+        RightControl,
+        RightAlt,
+        RightSuper,
+        Menu,
+    ]
+};
@@ -1,19 +1,36 @@
 //! Things available to spawn from the level editor
 //! Proto-mods, eventually some of the items will move to some sort of a wasm runtime
 
-mod machine_gun;
-mod muscet;
+mod armor;
+mod cannon;
+mod curse;
+mod environmental;
+mod explosive;
+mod galleon;
+mod grenades;
+mod gun;
+mod jellyfish;
+mod mines;
+mod shark_rain;
 mod sproinger;
 mod sword;
+mod volcano;
 
-use macroquad::{experimental::scene::HandleUntyped, math::Vec2};
+pub use sproinger::Sproinger;
+
+use macroquad::{
+    experimental::{collections::storage, scene::HandleUntyped},
+    math::Vec2,
+};
+
+use std::collections::HashMap;
 
 /// Proto-mod
 /// A meta description on how to create an item from the map
 pub struct Item {
     /// Tiled object name used on the objects layer, like "sword" or "sproinger"
-    pub tiled_name: &'static str,
-    pub constructor: fn(_: Vec2) -> HandleUntyped,
+    pub tiled_name: String,
+    pub constructor: Box<dyn Fn(Vec2) -> HandleUntyped>,
     /// Spawn offset from a tiled object position
     /// Mostly legacy, should be gone with a proper level editor
     /// may be will be a Vec2 soon, waiting for https://github.com/bitshifter/glam-rs/issues/76
@@ -21,62 +38,250 @@ pub struct Item {
     /// List of texture resources to load
     /// Later they will be accessible in resources.items_textures
     /// by tiled_name/resource_id
-    pub textures: &'static [(&'static str, &'static str)],
+    pub textures: Vec<(String, String)>,
     /// List of audio resources to load
     /// Later they will be accessible in resources.items_sounds
     /// by "tiled_name/resource_id"
-    pub sounds: &'static [(&'static str, &'static str)],
+    /// Weapons play "shoot", "reload", "empty" and "throw" ones, if they have them
+    pub sounds: Vec<(String, String)>,
     /// List of fxses to load. Each fx will be an EmitterCache, rendered
     /// in the world space
     /// Later they will be accessible in resources.items_fxses
     /// by "tiled_name/resource_id"
-    pub fxses: &'static [(&'static str, &'static str)],
+    pub fxses: Vec<(String, String)>,
     /// Right now items used in network play should be carefull
     /// about using random and similar things
-    /// It will be automatically tested and undetermenistic weapons will be denied
-    /// by the game itself, but, right now, its up for a weapon developer to veryfy
-    /// that nothing network-illegal is going on
+    /// "fishfight --check-items" simulates each item twice and reports
+    /// the items that diverged, CI fails if any of them is marked as network_ready
     pub network_ready: bool,
 }
 
-pub const ITEMS: &[Item] = &[
-    Item {
-        tiled_name: "sword",
-        constructor: sword::Sword::spawn,
-        tiled_offset: (-35., -25.),
-        textures: &[
-            ("sword", "assets/Whale/Sword(65x93).png"),
-            ("fish_sword", "assets/Whale/FishSword.png"),
-        ],
-        sounds: &[],
-        fxses: &[],
-        network_ready: true,
-    },
-    Item {
-        tiled_name: "sproinger",
-        constructor: sproinger::Sproinger::spawn,
-        tiled_offset: (-35., 0.),
-        textures: &[("sproinger", "assets/Whale/Sproinger.png")],
-        sounds: &[],
-        fxses: &[],
-        network_ready: true,
-    },
-    Item {
-        tiled_name: "muscet",
-        constructor: muscet::Muscet::spawn,
-        tiled_offset: (-35., -25.),
-        textures: &[("gun", "assets/Whale/Gun(92x32).png")],
-        sounds: &[],
-        fxses: &[],
-        network_ready: true,
-    },
-    Item {
-        tiled_name: "machine_gun",
-        constructor: machine_gun::MachineGun::spawn,
-        tiled_offset: (-35., -25.),
-        textures: &[("gun", "assets/Whale/MachineGun.png")],
-        sounds: &[],
-        fxses: &[],
-        network_ready: false,
-    },
-];
+/// All the items that can be spawned, by their "tiled_name"
+/// Filled once on startup with "load_items" and available in the storage
+#[derive(Default)]
+pub struct ItemRegistry {
+    items: Vec<Item>,
+    by_name: HashMap<String, usize>,
+}
+
+impl ItemRegistry {
+    pub fn register(&mut self, item: Item) -> Result<(), String> {
+        if self.by_name.contains_key(&item.tiled_name) {
+            return Err(format!(
+                "Item \"{}\" is already registered",
+                item.tiled_name
+            ));
+        }
+
+        self.by_name
+            .insert(item.tiled_name.clone(), self.items.len());
+        self.items.push(item);
+        Ok(())
+    }
+
+    pub fn get(&self, tiled_name: &str) -> Option<&Item> {
+        self.by_name.get(tiled_name).map(|ix| &self.items[*ix])
+    }
+
+    /// In the registration order
+    pub fn iter(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter()
+    }
+
+    /// ("tiled_name/resource_id", path) for one kind of resources of all the items,
+    /// like "registry.resources(|item| &item.textures)"
+    pub fn resources(&self, kind: fn(&Item) -> &Vec<(String, String)>) -> Vec<(String, String)> {
+        self.items
+            .iter()
+            .flat_map(|item| {
+                kind(item)
+                    .iter()
+                    .map(move |(id, path)| (format!("{}/{}", item.tiled_name, id), path.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Item by its "tiled_name", "load_items" should be already done
+pub fn spawn(tiled_name: &str, pos: Vec2) -> HandleUntyped {
+    let registry = storage::get::<ItemRegistry>();
+    let item = registry.get(tiled_name).expect("Unknown item");
+
+    (item.constructor)(pos)
+}
+
+fn resources(resources: &[(&str, &str)]) -> Vec<(String, String)> {
+    resources
+        .iter()
+        .map(|(id, path)| (id.to_string(), path.to_string()))
+        .collect()
+}
+
+/// Built-in items and the guns from assets/guns
+/// Should be done once, before anything tries to spawn or load items
+pub async fn load_items() {
+    let mut registry = ItemRegistry::default();
+
+    let builtin = vec![
+        Item {
+            tiled_name: "sword".to_string(),
+            constructor: Box::new(sword::Sword::spawn),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("sword", "assets/Whale/Sword(65x93).png"),
+                ("fish_sword", "assets/Whale/FishSword.png"),
+            ]),
+            sounds: resources(&[
+                ("shoot", "assets/sounds/sword.wav"),
+                ("throw", "assets/sounds/swing.wav"),
+            ]),
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "sproinger".to_string(),
+            constructor: Box::new(sproinger::Sproinger::spawn),
+            tiled_offset: (-35., 0.),
+            textures: resources(&[("sproinger", "assets/Whale/Sproinger.png")]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "curse".to_string(),
+            constructor: Box::new(|pos| {
+                environmental::EnvironmentalWeapon::spawn(&curse::CURSE, pos)
+            }),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("icon", "temp/Curse(32x32).png"),
+                ("curse", "temp/Curse(32x32).png"),
+            ]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "galleon".to_string(),
+            constructor: Box::new(|pos| {
+                environmental::EnvironmentalWeapon::spawn(&galleon::GALLEON, pos)
+            }),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("icon", "temp/GalleonIcon(32x29).png"),
+                ("galleon", "temp/FlyingGalleon(326x300).png"),
+            ]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "shark".to_string(),
+            constructor: Box::new(|pos| {
+                environmental::EnvironmentalWeapon::spawn(&shark_rain::SHARK_RAIN, pos)
+            }),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("icon", "temp/SharkIcon(32x34).png"),
+                ("shark", "temp/RainingShark(60x220).png"),
+            ]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "volcano".to_string(),
+            constructor: Box::new(|pos| {
+                environmental::EnvironmentalWeapon::spawn(&volcano::VOLCANO, pos)
+            }),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("icon", "temp/VolcanoIcon(36x22).png"),
+                ("volcano", "temp/EruptingVolcano(395x100).png"),
+            ]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "jellyfish".to_string(),
+            constructor: Box::new(jellyfish::Jellyfish::spawn),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("jellyfish", "temp/Jellyfish(32x29).png"),
+                ("flappy", "temp/FlappyJellyfish(50x51).png"),
+            ]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "cannon".to_string(),
+            constructor: Box::new(cannon::Cannon::spawn),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[
+                ("cannon", "assets/Whale/Cannon.png"),
+                ("cannonball", "temp/Cannonball(32x36).png"),
+            ]),
+            sounds: resources(&[
+                ("shoot", "assets/sounds/explode_m.wav"),
+                ("empty", "assets/sounds/gun_empty_b.wav"),
+                ("throw", "assets/sounds/swing.wav"),
+            ]),
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "grenades".to_string(),
+            constructor: Box::new(grenades::Grenades::spawn),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[("grenade", "assets/Whale/Grenades(15x15).png")]),
+            sounds: resources(&[
+                ("throw", "assets/sounds/throw_noiz.wav"),
+                ("empty", "assets/sounds/gun_empty.wav"),
+                ("explode", "assets/sounds/explode_m.wav"),
+            ]),
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "mines".to_string(),
+            constructor: Box::new(mines::Mines::spawn),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[("mine", "assets/Whale/Mines(30x15).png")]),
+            sounds: resources(&[
+                ("set", "assets/sounds/mine_set.wav"),
+                ("throw", "assets/sounds/swing.wav"),
+                ("empty", "assets/sounds/gun_empty.wav"),
+                ("explode", "assets/sounds/explode_m.wav"),
+            ]),
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "turtleshell".to_string(),
+            constructor: Box::new(armor::Armor::spawn_turtle_shell),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[("turtle_shell", "assets/Whale/TurtleShell(32x32).png")]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+        Item {
+            tiled_name: "shield".to_string(),
+            constructor: Box::new(armor::Armor::spawn_shield),
+            tiled_offset: (-35., -25.),
+            textures: resources(&[("shield", "assets/Whale/Shield(32x32).png")]),
+            sounds: vec![],
+            fxses: vec![],
+            network_ready: true,
+        },
+    ];
+    for item in builtin {
+        registry.register(item).unwrap();
+    }
+
+    gun::load_guns(&mut registry).await;
+
+    storage::store(registry);
+}
@@ -0,0 +1,199 @@
+//! Armor pickups: not weapons, the fish just wears them after touching
+//! Turtle shell goes on the back and is checked in Player::kill,
+//! shield goes in front and only stops bullets, see Bullet::update
+//! Both take two hits: the first one breaks them, the second one is the last.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::PhysicsBody,
+    nodes::Player,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    TurtleShell,
+    Shield,
+}
+
+impl Kind {
+    fn texture(self) -> &'static str {
+        match self {
+            Kind::TurtleShell => "turtleshell/turtle_shell",
+            Kind::Shield => "shield/shield",
+        }
+    }
+}
+
+/// Deleted as soon as a fish puts it on
+pub struct Armor {
+    kind: Kind,
+    texture: Texture2D,
+
+    pub body: PhysicsBody,
+}
+
+impl Armor {
+    pub const SIZE: f32 = 32.;
+    pub const HITS: i32 = 2;
+
+    pub fn spawn_turtle_shell(pos: Vec2) -> HandleUntyped {
+        Self::spawn(Kind::TurtleShell, pos)
+    }
+
+    pub fn spawn_shield(pos: Vec2) -> HandleUntyped {
+        Self::spawn(Kind::Shield, pos)
+    }
+
+    /// May be rebuilt by a rollback after being picked up,
+    /// so all the capabilities are provided right away
+    fn spawn(kind: Kind, pos: Vec2) -> HandleUntyped {
+        let armor = {
+            let mut resources = storage::get_mut::<Resources>();
+
+            let texture = resources.items_textures[kind.texture()];
+            let body = PhysicsBody::new(
+                &mut resources.collision_world,
+                pos,
+                0.0,
+                vec2(Self::SIZE, Self::SIZE),
+            );
+
+            Armor {
+                kind,
+                texture,
+                body,
+            }
+        };
+
+        let handle = capabilities::add_simulated_node(
+            armor,
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        );
+        scene::get_untyped_node(handle)
+            .unwrap()
+            .to_typed::<Armor>()
+            .provides(Self::physics_capabilities());
+
+        handle
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.body.update();
+
+        let hitbox = Rect::new(node.body.pos.x, node.body.pos.y, Self::SIZE, Self::SIZE);
+        for mut player in scene::find_nodes_by_type::<Player>() {
+            if player.dead || !hitbox.overlaps(&player.get_hitbox()) {
+                continue;
+            }
+
+            // a fish with intact armor has nothing to do with another one
+            let armor = match node.kind {
+                Kind::TurtleShell => &mut player.back_armor,
+                Kind::Shield => &mut player.front_armor,
+            };
+            if *armor < Self::HITS {
+                *armor = Self::HITS;
+                node.delete();
+                return;
+            }
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Armor>();
+            Armor::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Armor>();
+
+            writer.write(&(node.kind == Kind::Shield));
+            node.body.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Armor>();
+
+            // a rebuilt armor may be of any kind
+            node.kind = if reader.read() {
+                Kind::Shield
+            } else {
+                Kind::TurtleShell
+            };
+            node.texture = storage::get::<Resources>().items_textures[node.kind.texture()];
+            node.body.load(reader);
+        }
+
+        fn rebuild() -> HandleUntyped {
+            Armor::spawn_turtle_shell(vec2(0., 0.))
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(_: HandleUntyped) -> bool {
+            true
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Armor>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Armor>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Armor>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
+
+impl scene::Node for Armor {
+    fn draw(node: RefMut<Self>) {
+        draw_texture_ex(
+            node.texture,
+            node.body.pos.x,
+            node.body.pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(Self::SIZE, Self::SIZE)),
+                ..Default::default()
+            },
+        );
+    }
+}
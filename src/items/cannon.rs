@@ -0,0 +1,438 @@
+//! A cannon firing heavy bouncing cannonballs
+//! Unlike bullets, cannonballs are physics bodies: they fall and bounce off the walls
+
+use macroquad::{
+    experimental::{
+        animation::Animation,
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::{
+        Ammo, AmmoHud, GunlikeAnimation, PhysicsBody, SimulatedSprite, ThrowableItem, WhenEmpty,
+    },
+    nodes::Player,
+    simulation::{self, FRAME_TIME},
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub struct Cannonball {
+    body: PhysicsBody,
+    owner: u8,
+    lived: f32,
+    texture: Texture2D,
+}
+
+impl Cannonball {
+    const SIZE: f32 = 32.;
+    const SPEED: f32 = 800.;
+    const BOUNCYNESS: f32 = 0.6;
+    const LIFETIME: f32 = 3.;
+    /// The owner is safe for a moment after the shot, after that - not really
+    const ARMING_TIME: f32 = 0.2;
+
+    pub fn spawn(pos: Vec2, facing: bool, owner: u8) -> HandleUntyped {
+        let mut body = Self::body(pos);
+        body.facing = facing;
+        body.speed = vec2(if facing { Self::SPEED } else { -Self::SPEED }, -200.);
+
+        capabilities::add_simulated_node(
+            Cannonball {
+                body,
+                owner,
+                lived: 0.,
+                texture: storage::get::<Resources>().items_textures["cannon/cannonball"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn body(pos: Vec2) -> PhysicsBody {
+        let mut resources = storage::get_mut::<Resources>();
+        let mut body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::SIZE, Self::SIZE),
+        );
+        body.bouncyness = Self::BOUNCYNESS;
+        body
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.lived += FRAME_TIME;
+        if node.lived > Self::LIFETIME {
+            node.delete();
+            return;
+        }
+
+        node.body.update();
+
+        let hitbox = Rect::new(node.body.pos.x, node.body.pos.y, Self::SIZE, Self::SIZE);
+        for mut player in scene::find_nodes_by_type::<Player>() {
+            if player.dead || (player.id == node.owner && node.lived < Self::ARMING_TIME) {
+                continue;
+            }
+
+            if hitbox.overlaps(&player.get_hitbox()) {
+                let direction = node.body.pos.x > player.body.pos.x + 10.;
+                player.kill(direction, Some(node.owner));
+
+                let mut resources = storage::get_mut::<Resources>();
+                if let Some(effects) = resources.effects.as_mut() {
+                    effects.explosion_fxses.spawn(node.body.pos);
+                }
+
+                node.delete();
+                return;
+            }
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannonball>();
+            Cannonball::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannonball>();
+
+            node.body.save(writer);
+            writer.write(&node.owner);
+            writer.write(&node.lived);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannonball>();
+
+            node.body.load(reader);
+            node.owner = reader.read();
+            node.lived = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            Cannonball::spawn(vec2(0., 0.), true, 0)
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for Cannonball {
+    fn draw(node: RefMut<Self>) {
+        // the fuse is sticking out of the top
+        draw_texture_ex(
+            node.texture,
+            node.body.pos.x,
+            node.body.pos.y - 4.,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(32., 36.)),
+                flip_x: !node.body.facing,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+pub struct Cannon {
+    sprite: GunlikeAnimation,
+
+    pub ammo: Ammo,
+
+    pub body: PhysicsBody,
+    pub throwable: ThrowableItem,
+}
+
+impl Cannon {
+    pub const COLLIDER_WIDTH: f32 = 50.0;
+    pub const COLLIDER_HEIGHT: f32 = 30.0;
+    pub const GUN_THROWBACK: f32 = 500.0;
+    pub const CANNONBALLS: i32 = 3;
+
+    pub fn spawn(pos: Vec2) -> HandleUntyped {
+        let mut resources = storage::get_mut::<Resources>();
+
+        let sprite = GunlikeAnimation::new(
+            SimulatedSprite::new(
+                88,
+                36,
+                &[
+                    Animation {
+                        name: "idle".to_string(),
+                        row: 0,
+                        frames: 1,
+                        fps: 1,
+                    },
+                    Animation {
+                        name: "shoot".to_string(),
+                        row: 1,
+                        frames: 4,
+                        fps: 15,
+                    },
+                ],
+                false,
+            ),
+            resources.items_textures["cannon/cannon"],
+            Self::COLLIDER_WIDTH,
+        );
+
+        let body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::COLLIDER_WIDTH, Self::COLLIDER_HEIGHT),
+        );
+
+        scene::add_node(Cannon {
+            sprite,
+            ammo: Ammo::new(Self::CANNONBALLS, AmmoHud::Circles, WhenEmpty::Keep),
+            body,
+            throwable: ThrowableItem::default(),
+        })
+        .untyped()
+    }
+
+    pub fn throw(&mut self, force: bool) {
+        self.throwable.throw(&mut self.body, force);
+
+        if force {
+            Self::play_sound("throw");
+        }
+    }
+
+    fn play_sound(id: &str) {
+        let resources = storage::get::<Resources>();
+        if let Some(effects) = &resources.effects {
+            effects.play_item_sound("cannon", id);
+        }
+    }
+
+    pub fn shoot(node: Handle<Cannon>, player: Handle<Player>, frame: u32) -> bool {
+        let frame_duration = simulation::frames(0.08);
+
+        if frame == 0 {
+            let node = &mut *scene::get_node(node);
+            if node.ammo.is_empty() {
+                Self::play_sound("empty");
+
+                return true;
+            }
+
+            let player = &mut *scene::get_node(player);
+
+            Self::play_sound("shoot");
+
+            let muzzle = if node.body.facing {
+                node.body.pos + vec2(Self::COLLIDER_WIDTH, -4.)
+            } else {
+                node.body.pos + vec2(-Cannonball::SIZE, -4.)
+            };
+            Cannonball::spawn(muzzle, node.body.facing, player.id);
+
+            player.body.speed.x = -Self::GUN_THROWBACK * player.body.facing_dir().x;
+
+            node.sprite.set_animation(1);
+        }
+
+        let node = &mut *scene::get_node(node);
+        if frame < 4 * frame_duration {
+            node.sprite.set_frame(frame / frame_duration);
+            false
+        } else {
+            node.sprite.set_animation(0);
+            node.ammo.take();
+            true
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        node.sprite.update();
+        node.throwable.update(&mut node.body, true);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+            Cannon::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+
+            node.sprite.save(writer);
+            node.ammo.save(writer);
+            node.body.save(writer);
+            node.throwable.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+
+            node.sprite.load(reader);
+            node.ammo.load(reader);
+            node.body.load(reader);
+            node.throwable.load(reader);
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
+    fn weapon_capabilities() -> capabilities::Weapon {
+        fn throw(node: HandleUntyped, force: bool) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Cannon>();
+
+            Cannon::throw(&mut node, force);
+        }
+
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Cannon>()
+                .handle();
+
+            Cannon::shoot(node, player, frame)
+        }
+
+        fn is_thrown(node: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(node).unwrap().to_typed::<Cannon>();
+
+            node.throwable.thrown()
+        }
+
+        fn pick_up(node: HandleUntyped, owner: Handle<Player>) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Cannon>();
+
+            node.body.angle = 0.;
+            node.throwable.owner = Some(owner);
+        }
+
+        fn mount(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Cannon>();
+            let mount_pos = if node.body.facing {
+                vec2(0., 20.)
+            } else {
+                vec2(-20., 20.)
+            };
+
+            node.body.pos = parent_pos + mount_pos;
+            node.body.facing = parent_facing;
+        }
+
+        fn collider(node: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(node).unwrap().to_typed::<Cannon>();
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                Cannon::COLLIDER_WIDTH,
+                Cannon::COLLIDER_HEIGHT,
+            )
+        }
+
+        capabilities::Weapon {
+            collider,
+            mount,
+            aim: capabilities::Weapon::no_aim,
+            is_thrown,
+            pick_up,
+            throw,
+            shoot,
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(handle: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+
+            node.throwable.owner.is_none()
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Cannon>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
+
+impl scene::Node for Cannon {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::weapon_capabilities());
+        node.provides(Self::physics_capabilities());
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
+    }
+
+    fn draw(node: RefMut<Self>) {
+        node.sprite
+            .draw(node.body.pos, node.body.facing, node.body.angle);
+
+        if !node.throwable.thrown() {
+            node.ammo.draw_hud(node.body.pos);
+        }
+    }
+}
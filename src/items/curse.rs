@@ -0,0 +1,151 @@
+//! A skull chasing the closest enemy in a sinusoidal motion, for a limited time
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    items::environmental::{self, Summon},
+    nodes::Player,
+    simulation::FRAME_TIME,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub const CURSE: Summon = Summon {
+    tiled_name: "curse",
+    size: (32., 32.),
+    summon: Curse::spawn,
+};
+
+pub struct Curse {
+    /// Where the skull would be without the wobble
+    pos: Vec2,
+    owner: u8,
+    lived: f32,
+    texture: Texture2D,
+}
+
+impl Curse {
+    const SIZE: f32 = 32.;
+    const SPEED: f32 = 150.;
+    const LIFETIME: f32 = 6.;
+    const WOBBLE_AMPLITUDE: f32 = 30.;
+    /// Radians per second
+    const WOBBLE_SPEED: f32 = 8.;
+
+    pub fn spawn(owner: u8, pos: Vec2) -> HandleUntyped {
+        capabilities::add_simulated_node(
+            Curse {
+                pos: pos - vec2(0., Self::SIZE),
+                owner,
+                lived: 0.,
+                texture: storage::get::<Resources>().items_textures["curse/curse"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn wobbled_pos(&self) -> Vec2 {
+        self.pos
+            + vec2(
+                0.,
+                (self.lived * Self::WOBBLE_SPEED).sin() * Self::WOBBLE_AMPLITUDE,
+            )
+    }
+
+    fn closest_enemy(&self) -> Option<Vec2> {
+        scene::find_nodes_by_type::<Player>()
+            .filter(|player| !player.dead && !player.eliminated && player.id != self.owner)
+            .map(|player| {
+                let hitbox = player.get_hitbox();
+                hitbox.point() + hitbox.size() / 2.
+            })
+            .min_by(|a, b| {
+                let a = a.distance_squared(self.pos);
+                let b = b.distance_squared(self.pos);
+                a.partial_cmp(&b).unwrap()
+            })
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.lived += FRAME_TIME;
+        if node.lived > Self::LIFETIME {
+            node.delete();
+            return;
+        }
+
+        // no enemy around - just hang in the air and wait
+        if let Some(target) = node.closest_enemy() {
+            let center = node.pos + vec2(Self::SIZE, Self::SIZE) / 2.;
+            let direction = (target - center).normalize_or_zero();
+            node.pos += direction * Self::SPEED * FRAME_TIME;
+        }
+
+        let pos = node.wobbled_pos();
+        let rect = Rect::new(pos.x, pos.y, Self::SIZE, Self::SIZE);
+        if environmental::kill_in_rect(rect, node.owner) {
+            node.delete();
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Curse>();
+            Curse::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Curse>();
+
+            writer.write_vec2(node.pos);
+            writer.write(&node.owner);
+            writer.write(&node.lived);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Curse>();
+
+            node.pos = reader.read_vec2();
+            node.owner = reader.read();
+            node.lived = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            Curse::spawn(0, vec2(0., 0.))
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for Curse {
+    fn draw(node: RefMut<Self>) {
+        let pos = node.wobbled_pos();
+
+        draw_texture_ex(
+            node.texture,
+            pos.x,
+            pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(Self::SIZE, Self::SIZE)),
+                ..Default::default()
+            },
+        );
+    }
+}
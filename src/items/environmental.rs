@@ -0,0 +1,330 @@
+//! Environmental weapons: single use items summoning something big
+//! See docs/environmental_weapons.md
+//!
+//! The item itself is the same for all of them - an icon to pick up, hold and throw.
+//! The only difference is what is summoned on "shoot".
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::{PhysicsBody, ThrowableItem},
+    items::{curse::CURSE, galleon::GALLEON, shark_rain::SHARK_RAIN, volcano::VOLCANO},
+    nodes::Player,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub struct Summon {
+    /// "tiled_name" of the item, the icon is "tiled_name/icon"
+    pub tiled_name: &'static str,
+    pub size: (f32, f32),
+    /// Called once, with the id and the position of the fish that used the item
+    pub summon: fn(owner: u8, pos: Vec2) -> HandleUntyped,
+}
+
+/// Snapshots refer to the summons by index in this list
+const SUMMONS: &[&Summon] = &[&CURSE, &GALLEON, &SHARK_RAIN, &VOLCANO];
+
+pub struct EnvironmentalWeapon {
+    summon: &'static Summon,
+    icon: Texture2D,
+
+    pub body: PhysicsBody,
+    pub throwable: ThrowableItem,
+}
+
+impl EnvironmentalWeapon {
+    /// All the summons share the collider, so a rolled back item may switch
+    /// its summon and keep the body. The icons are drawn on its bottom edge.
+    const COLLIDER_SIZE: (f32, f32) = (32., 32.);
+
+    fn new(summon: &'static Summon, pos: Vec2) -> EnvironmentalWeapon {
+        let mut resources = storage::get_mut::<Resources>();
+
+        let body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::COLLIDER_SIZE.0, Self::COLLIDER_SIZE.1),
+        );
+        drop(resources);
+
+        EnvironmentalWeapon {
+            summon,
+            icon: Self::icon(summon),
+            body,
+            throwable: ThrowableItem::default(),
+        }
+    }
+
+    fn icon(summon: &Summon) -> Texture2D {
+        storage::get::<Resources>().items_textures[&format!("{}/icon", summon.tiled_name)]
+    }
+
+    fn icon_pos(&self) -> Vec2 {
+        self.body.pos + vec2(0., Self::COLLIDER_SIZE.1 - self.summon.size.1)
+    }
+
+    /// The item is deleted once used, and may be rebuilt by a rollback,
+    /// so all the capabilities are provided right away
+    pub fn spawn(summon: &'static Summon, pos: Vec2) -> HandleUntyped {
+        let handle = capabilities::add_simulated_node(
+            Self::new(summon, pos),
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        );
+
+        let mut node = scene::get_untyped_node(handle)
+            .unwrap()
+            .to_typed::<EnvironmentalWeapon>();
+        node.provides(Self::weapon_capabilities());
+        node.provides(Self::physics_capabilities());
+
+        handle
+    }
+
+    pub fn throw(&mut self, force: bool) {
+        self.throwable.throw(&mut self.body, force);
+    }
+
+    /// Single use, the item is gone right after
+    pub fn shoot(node: Handle<EnvironmentalWeapon>, player: Handle<Player>, _frame: u32) -> bool {
+        let node = scene::get_node(node);
+        let player = &mut *scene::get_node(player);
+
+        (node.summon.summon)(player.id, player.body.pos);
+
+        player.weapon = None;
+        node.delete();
+
+        true
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        node.throwable.update(&mut node.body, true);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+            EnvironmentalWeapon::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            let summon = SUMMONS
+                .iter()
+                .position(|summon| summon.tiled_name == node.summon.tiled_name)
+                .unwrap();
+            writer.write(&(summon as u32));
+            node.body.save(writer);
+            node.throwable.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            // a rebuilt item may be of any summon
+            let summon = SUMMONS[reader.read::<u32>() as usize];
+            if summon.tiled_name != node.summon.tiled_name {
+                node.summon = summon;
+                node.icon = EnvironmentalWeapon::icon(summon);
+            }
+            node.body.load(reader);
+            node.throwable.load(reader);
+        }
+
+        fn rebuild() -> HandleUntyped {
+            EnvironmentalWeapon::spawn(SUMMONS[0], vec2(0., 0.))
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+
+    fn weapon_capabilities() -> capabilities::Weapon {
+        fn throw(node: HandleUntyped, force: bool) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            EnvironmentalWeapon::throw(&mut node, force);
+        }
+
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>()
+                .handle();
+
+            EnvironmentalWeapon::shoot(node, player, frame)
+        }
+
+        fn is_thrown(node: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            node.throwable.thrown()
+        }
+
+        fn pick_up(node: HandleUntyped, owner: Handle<Player>) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            node.body.angle = 0.;
+            node.throwable.owner = Some(owner);
+        }
+
+        fn mount(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            // the icon stays where it was before the shared collider
+            let y = 16. + node.summon.size.1 - EnvironmentalWeapon::COLLIDER_SIZE.1;
+            let mount_pos = if parent_facing {
+                vec2(20., y)
+            } else {
+                vec2(-node.summon.size.0 + 56., y)
+            };
+
+            node.body.pos = parent_pos + mount_pos;
+            node.body.facing = parent_facing;
+        }
+
+        fn collider(node: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            let pos = node.icon_pos();
+            Rect::new(pos.x, pos.y, node.summon.size.0, node.summon.size.1)
+        }
+
+        capabilities::Weapon {
+            collider,
+            mount,
+            aim: capabilities::Weapon::no_aim,
+            is_thrown,
+            pick_up,
+            throw,
+            shoot,
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(handle: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            node.throwable.owner.is_none()
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EnvironmentalWeapon>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
+
+impl scene::Node for EnvironmentalWeapon {
+    fn draw(node: RefMut<Self>) {
+        let pos = node.icon_pos();
+        draw_texture_ex(
+            node.icon,
+            pos.x,
+            pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(node.summon.size.0, node.summon.size.1)),
+                flip_x: !node.body.facing,
+                rotation: node.body.angle,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Map size in pixels, summoned things come from its edges
+pub fn map_size() -> Vec2 {
+    let resources = storage::get::<Resources>();
+    let map = &resources.tiled_map.raw_tiled_map;
+
+    vec2(
+        (map.tilewidth * map.width) as f32,
+        (map.tileheight * map.height) as f32,
+    )
+}
+
+/// Kill everyone but the owner touching the rect, returns true if anybody was hit
+/// Dead fishes are skipped: summoned things are big and would kill the same
+/// fish again each frame
+pub fn kill_in_rect(rect: Rect, owner: u8) -> bool {
+    let mut hit = false;
+
+    for mut player in scene::find_nodes_by_type::<Player>() {
+        if player.dead || player.eliminated || player.id == owner {
+            continue;
+        }
+
+        if rect.overlaps(&player.get_hitbox()) {
+            let direction = rect.x + rect.w / 2. > player.body.pos.x + 10.;
+            player.kill(direction, Some(owner));
+            hit = true;
+        }
+    }
+
+    hit
+}
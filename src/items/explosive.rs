@@ -0,0 +1,62 @@
+//! Things going boom: shared by grenades, mines and anything else exploding
+
+use macroquad::{
+    experimental::{collections::storage, scene},
+    prelude::*,
+};
+
+use crate::{
+    capabilities::{PhysicsObject, PhysicsObjectTrait},
+    nodes::{Camera, Player},
+    Resources,
+};
+
+/// Speed given to everything caught in the blast, players included
+const KNOCKBACK: f32 = 900.;
+
+/// Kill all the fishes in the square around "center", including the owner - explosions
+/// are not picky. Everything else with a physics body is pushed away from the center.
+pub fn explode(center: Vec2, radius: f32, owner: u8) {
+    let rect = Rect::new(
+        center.x - radius,
+        center.y - radius,
+        radius * 2.,
+        radius * 2.,
+    );
+
+    for mut player in scene::find_nodes_by_type::<Player>() {
+        if player.dead || player.eliminated {
+            continue;
+        }
+
+        if rect.overlaps(&player.get_hitbox()) {
+            let direction = center.x > player.body.pos.x + 10.;
+            player.kill(direction, Some(owner));
+        }
+    }
+
+    // players are physics objects too, so dead fishes fly away
+    for physics_object in scene::find_nodes_with::<PhysicsObject>().filter(|obj| obj.active()) {
+        let collider = physics_object.collider();
+        if !rect.overlaps(&collider) {
+            continue;
+        }
+
+        let object_center = collider.point() + collider.size() / 2.;
+        let direction = (object_center - center).normalize_or_zero();
+        // mostly up, otherwise things on the ground would just slide
+        let push = vec2(direction.x, direction.y.min(-0.5)).normalize() * KNOCKBACK;
+
+        physics_object.set_speed_x(push.x);
+        physics_object.set_speed_y(push.y);
+    }
+
+    scene::find_node_by_type::<Camera>()
+        .unwrap()
+        .shake_noise(2.0, 20, 1.);
+
+    let mut resources = storage::get_mut::<Resources>();
+    if let Some(effects) = resources.effects.as_mut() {
+        effects.explosion_fxses.spawn(center);
+    }
+}
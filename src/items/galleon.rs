@@ -0,0 +1,148 @@
+//! A large flying ship crossing the whole map horizontally
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    items::environmental::{self, Summon},
+    simulation::{self, FRAME_TIME},
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub const GALLEON: Summon = Summon {
+    tiled_name: "galleon",
+    size: (32., 29.),
+    summon: FlyingGalleon::spawn,
+};
+
+pub struct FlyingGalleon {
+    pos: Vec2,
+    /// true - from left to right
+    direction: bool,
+    owner: u8,
+    texture: Texture2D,
+}
+
+impl FlyingGalleon {
+    const WIDTH: f32 = 326.;
+    const HEIGHT: f32 = 300.;
+    const SPEED: f32 = 250.;
+    /// The sprite has some empty space around the ship
+    const HITBOX_INSET: f32 = 20.;
+
+    pub fn spawn(owner: u8, _pos: Vec2) -> HandleUntyped {
+        let (pos, direction) = Self::start_position_data();
+
+        capabilities::add_simulated_node(
+            FlyingGalleon {
+                pos,
+                direction,
+                owner,
+                texture: storage::get::<Resources>().items_textures["galleon/galleon"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    /// Random side of the map and random height
+    fn start_position_data() -> (Vec2, bool) {
+        let map_size = environmental::map_size();
+
+        let (start_x, direction) = if simulation::gen_range(0., 1.) < 0.5 {
+            (-Self::WIDTH, true)
+        } else {
+            (map_size.x, false)
+        };
+
+        let start_y = simulation::gen_range(0., (map_size.y - Self::HEIGHT).max(0.));
+
+        (vec2(start_x, start_y), direction)
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let direction = if node.direction { 1. } else { -1. };
+        node.pos.x += direction * Self::SPEED * FRAME_TIME;
+
+        let map_width = environmental::map_size().x;
+        if node.pos.x > map_width || node.pos.x < -Self::WIDTH {
+            node.delete();
+            return;
+        }
+
+        let hitbox = Rect::new(
+            node.pos.x + Self::HITBOX_INSET,
+            node.pos.y + Self::HITBOX_INSET,
+            Self::WIDTH - Self::HITBOX_INSET * 2.,
+            Self::HEIGHT - Self::HITBOX_INSET * 2.,
+        );
+        environmental::kill_in_rect(hitbox, node.owner);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<FlyingGalleon>();
+            FlyingGalleon::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<FlyingGalleon>();
+
+            writer.write_vec2(node.pos);
+            writer.write(&node.direction);
+            writer.write(&node.owner);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<FlyingGalleon>();
+
+            node.pos = reader.read_vec2();
+            node.direction = reader.read();
+            node.owner = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            FlyingGalleon::spawn(0, vec2(0., 0.))
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for FlyingGalleon {
+    fn draw(node: RefMut<Self>) {
+        draw_texture_ex(
+            node.texture,
+            node.pos.x,
+            node.pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(Self::WIDTH, Self::HEIGHT)),
+                // the ship on the sprite is sailing to the left
+                flip_x: node.direction,
+                ..Default::default()
+            },
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! A bag of grenades: "shoot" lobs a grenade, it bounces around for a bit and explodes
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::{Ammo, AmmoHud, PhysicsBody, ThrowableItem, WhenEmpty},
+    items::explosive,
+    nodes::Player,
+    simulation::FRAME_TIME,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+fn play_sound(id: &str) {
+    let resources = storage::get::<Resources>();
+    if let Some(effects) = &resources.effects {
+        effects.play_item_sound("grenades", id);
+    }
+}
+
+pub struct ArmedGrenade {
+    body: PhysicsBody,
+    owner: u8,
+    lived: f32,
+    texture: Texture2D,
+}
+
+impl ArmedGrenade {
+    const SIZE: f32 = 15.;
+    const FUSE: f32 = 1.5;
+    const BOUNCYNESS: f32 = 0.5;
+    const EXPLOSION_RADIUS: f32 = 60.;
+
+    pub fn spawn(pos: Vec2, speed: Vec2, owner: u8) -> HandleUntyped {
+        let mut body = Self::body(pos);
+        body.speed = speed;
+        body.facing = speed.x >= 0.;
+
+        capabilities::add_simulated_node(
+            ArmedGrenade {
+                body,
+                owner,
+                lived: 0.,
+                texture: storage::get::<Resources>().items_textures["grenades/grenade"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn body(pos: Vec2) -> PhysicsBody {
+        let mut resources = storage::get_mut::<Resources>();
+        let mut body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::SIZE, Self::SIZE),
+        );
+        body.bouncyness = Self::BOUNCYNESS;
+        body
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.body.update();
+        node.lived += FRAME_TIME;
+
+        if node.lived > Self::FUSE {
+            let center = node.body.pos + node.body.size / 2.;
+            explosive::explode(center, Self::EXPLOSION_RADIUS, node.owner);
+            play_sound("explode");
+
+            node.delete();
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ArmedGrenade>();
+            ArmedGrenade::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ArmedGrenade>();
+
+            node.body.save(writer);
+            writer.write(&node.owner);
+            writer.write(&node.lived);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ArmedGrenade>();
+
+            node.body.load(reader);
+            node.owner = reader.read();
+            node.lived = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            ArmedGrenade::spawn(vec2(0., 0.), vec2(0., 0.), 0)
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for ArmedGrenade {
+    fn draw(node: RefMut<Self>) {
+        // spinning while flying, blinking faster and faster when about to explode
+        let rotation = if node.body.on_ground {
+            0.
+        } else {
+            node.lived * 10.
+        };
+        let blink = ((node.lived * node.lived * 8.) as i32) % 2 == 0;
+        let color = if blink { WHITE } else { RED };
+
+        draw_texture_ex(
+            node.texture,
+            node.body.pos.x,
+            node.body.pos.y,
+            color,
+            DrawTextureParams {
+                dest_size: Some(vec2(Self::SIZE, Self::SIZE)),
+                flip_x: !node.body.facing,
+                rotation,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+pub struct Grenades {
+    texture: Texture2D,
+
+    pub ammo: Ammo,
+
+    pub body: PhysicsBody,
+    pub throwable: ThrowableItem,
+}
+
+impl Grenades {
+    pub const SIZE: f32 = 15.;
+    pub const GRENADES: i32 = 3;
+    const THROW_SPEED: f32 = 500.;
+
+    pub fn spawn(pos: Vec2) -> HandleUntyped {
+        let mut resources = storage::get_mut::<Resources>();
+
+        let texture = resources.items_textures["grenades/grenade"];
+        let body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::SIZE, Self::SIZE),
+        );
+
+        scene::add_node(Grenades {
+            texture,
+            ammo: Ammo::new(Self::GRENADES, AmmoHud::Circles, WhenEmpty::Drop),
+            body,
+            throwable: ThrowableItem::default(),
+        })
+        .untyped()
+    }
+
+    pub fn throw(&mut self, force: bool) {
+        self.throwable.throw(&mut self.body, force);
+
+        if force {
+            play_sound("throw");
+        }
+    }
+
+    pub fn shoot(node: Handle<Grenades>, player: Handle<Player>, _frame: u32) -> bool {
+        let node = &mut *scene::get_node(node);
+        let player = &mut *scene::get_node(player);
+
+        if node.ammo.is_empty() {
+            play_sound("empty");
+        } else {
+            node.ammo.take();
+
+            let speed = vec2(
+                Self::THROW_SPEED * player.body.facing_dir().x + player.body.speed.x,
+                -Self::THROW_SPEED,
+            );
+            ArmedGrenade::spawn(node.body.pos, speed, player.id);
+
+            play_sound("throw");
+        }
+
+        if let Some(force) = node.ammo.throw_away() {
+            node.throw(force);
+            player.weapon = None;
+        }
+
+        true
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        node.throwable.update(&mut node.body, true);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+            Grenades::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            node.ammo.save(writer);
+            node.body.save(writer);
+            node.throwable.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            node.ammo.load(reader);
+            node.body.load(reader);
+            node.throwable.load(reader);
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
+    fn weapon_capabilities() -> capabilities::Weapon {
+        fn throw(node: HandleUntyped, force: bool) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            Grenades::throw(&mut node, force);
+        }
+
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Grenades>()
+                .handle();
+
+            Grenades::shoot(node, player, frame)
+        }
+
+        fn is_thrown(node: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            node.throwable.thrown()
+        }
+
+        fn pick_up(node: HandleUntyped, owner: Handle<Player>) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            node.body.angle = 0.;
+            node.throwable.owner = Some(owner);
+        }
+
+        fn mount(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            let mount_pos = if parent_facing {
+                vec2(30., 24.)
+            } else {
+                vec2(10., 24.)
+            };
+
+            node.body.pos = parent_pos + mount_pos;
+            node.body.facing = parent_facing;
+        }
+
+        fn collider(node: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                Grenades::SIZE,
+                Grenades::SIZE,
+            )
+        }
+
+        capabilities::Weapon {
+            collider,
+            mount,
+            aim: capabilities::Weapon::no_aim,
+            is_thrown,
+            pick_up,
+            throw,
+            shoot,
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(handle: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            node.throwable.owner.is_none()
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Grenades>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
+
+impl scene::Node for Grenades {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::weapon_capabilities());
+        node.provides(Self::physics_capabilities());
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
+    }
+
+    fn draw(node: RefMut<Self>) {
+        draw_texture_ex(
+            node.texture,
+            node.body.pos.x,
+            node.body.pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(Self::SIZE, Self::SIZE)),
+                flip_x: !node.body.facing,
+                rotation: node.body.angle,
+                ..Default::default()
+            },
+        );
+
+        if !node.throwable.thrown() {
+            node.ammo.draw_hud(node.body.pos);
+        }
+    }
+}
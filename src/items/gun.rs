@@ -0,0 +1,570 @@
+//! Gun made out of a description file
+//!
+//! All the guns are pretty much the same: a spritesheet, a magazine and a bullet
+//! with some speed. So instead of a module per gun, they are described
+//! in assets/guns/*.json, listed in assets/guns/guns.toml, and this one node
+//! does all the work.
+
+use macroquad::{
+    experimental::{
+        animation::Animation,
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use nanoserde::DeJson;
+
+use crate::{
+    capabilities,
+    components::{
+        Ammo, AmmoHud, Bullet, GunlikeAnimation, PhysicsBody, SimulatedSprite, ThrowableItem,
+        WhenEmpty,
+    },
+    input::Aim,
+    items::{Item, ItemRegistry},
+    nodes::Player,
+    simulation,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+#[derive(Debug, Clone, Default, DeJson)]
+pub struct AnimationDesc {
+    pub row: u32,
+    pub frames: u32,
+    pub fps: u32,
+}
+
+#[derive(Debug, Clone, DeJson)]
+pub struct GunDesc {
+    /// Tiled object name, resources are available by "name/resource_id",
+    /// the spritesheet is "name/gun"
+    pub name: String,
+    /// Same as "Item::tiled_offset"
+    pub tiled_offset: (f32, f32),
+    /// See "GunlikeAnimation" on how the spritesheet should look like
+    pub texture: String,
+    pub sprite_size: (u32, u32),
+    pub idle: AnimationDesc,
+    pub shoot: AnimationDesc,
+    /// Drawn on top of the gun while shooting, from the same spritesheet
+    /// Guns without one just leave it out
+    #[nserde(default)]
+    pub muzzle_flash: AnimationDesc,
+    /// How long each frame of the "shoot" animation holds the fish
+    pub shoot_frame_time: f32,
+    pub collider: (f32, f32),
+    /// Gun position relative to the fish, facing right and left
+    pub mount_offset: (f32, f32),
+    pub mount_offset_left: (f32, f32),
+    /// Bullets in a freshly spawned gun, never refilled
+    pub bullets: i32,
+    pub bullet_speed: f32,
+    pub bullet_lifetime: f32,
+    pub bullet_spread: f32,
+    pub bullet_size: f32,
+    /// Bullets are spawned at gun position + muzzle_offset + facing * muzzle_distance
+    pub muzzle_offset: (f32, f32),
+    pub muzzle_distance: f32,
+    /// Fish speed kick in the opposite direction on each shot
+    pub throwback: f32,
+    /// "circles", "lines" or "bar", anything else - no bullets shown
+    pub hud: String,
+    /// "drop" or "throw" to get rid of the gun after the last bullet,
+    /// by default it is kept and just clicks
+    #[nserde(default)]
+    pub when_empty: String,
+    /// Same as "Item::sounds"
+    #[nserde(default)]
+    pub sounds: Vec<(String, String)>,
+    /// Same as "Item::fxses", "shoot" one is spawned on the muzzle on each shot
+    #[nserde(default)]
+    pub fxses: Vec<(String, String)>,
+    /// Same as "Item::network_ready"
+    pub network_ready: bool,
+}
+
+/// Register all the guns from assets/guns/guns.toml
+pub async fn load_guns(registry: &mut ItemRegistry) {
+    let guns_str = load_string("assets/guns/guns.toml").await.unwrap();
+    let toml = nanoserde::TomlParser::parse(&guns_str).unwrap();
+
+    for gun in toml["gun"].arr() {
+        let path = gun["path"].str();
+        let json = load_string(path).await.unwrap();
+        let desc =
+            GunDesc::deserialize_json(&json).unwrap_or_else(|err| panic!("{}: {:?}", path, err));
+
+        let item = Item {
+            tiled_name: desc.name.clone(),
+            tiled_offset: desc.tiled_offset,
+            textures: vec![("gun".to_string(), desc.texture.clone())],
+            sounds: desc.sounds.clone(),
+            fxses: desc.fxses.clone(),
+            network_ready: desc.network_ready,
+            constructor: Box::new(move |pos| Gun::spawn(&desc, pos)),
+        };
+        registry
+            .register(item)
+            .unwrap_or_else(|err| panic!("{}: {}", path, err));
+    }
+}
+
+pub struct GunBullet {
+    bullet: Bullet,
+    size: f32,
+}
+
+impl GunBullet {
+    pub fn spawn(desc: &GunDesc, pos: Vec2, direction: Vec2, owner: u8) -> HandleUntyped {
+        capabilities::add_simulated_node(
+            GunBullet {
+                bullet: Bullet::new(
+                    pos,
+                    desc.bullet_lifetime,
+                    direction,
+                    desc.bullet_speed,
+                    desc.bullet_spread,
+                    owner,
+                ),
+                size: desc.bullet_size,
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<GunBullet>();
+            GunBullet::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<GunBullet>();
+
+            node.bullet.save(writer);
+            // same node for bullets of all the guns, so it is not a constant anymore
+            writer.write(&node.bullet.lifetime);
+            writer.write(&node.size);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<GunBullet>();
+
+            node.bullet.load(reader);
+            node.bullet.lifetime = reader.read();
+            node.size = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            capabilities::add_simulated_node(
+                GunBullet {
+                    bullet: Bullet::new(vec2(0., 0.), 0., vec2(1., 0.), 0., 0., 0),
+                    size: 0.,
+                },
+                GunBullet::network_capabilities(),
+                GunBullet::snapshot_capabilities(),
+            )
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        if !node.bullet.update() {
+            node.delete();
+        }
+    }
+}
+
+impl scene::Node for GunBullet {
+    fn draw(node: RefMut<Self>) {
+        draw_circle(
+            node.bullet.pos.x,
+            node.bullet.pos.y,
+            node.size,
+            Color::new(1.0, 1.0, 0.8, 1.0),
+        );
+    }
+}
+
+pub struct Gun {
+    desc: GunDesc,
+
+    sprite: GunlikeAnimation,
+    muzzle_flash_sprite: Option<GunlikeAnimation>,
+    muzzle_flash: bool,
+    /// Owner's aim, None - shooting straight ahead
+    aim: Option<Aim>,
+
+    pub ammo: Ammo,
+
+    pub body: PhysicsBody,
+    pub throwable: ThrowableItem,
+}
+
+impl scene::Node for Gun {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::weapon_capabilities());
+        node.provides(Self::physics_capabilities());
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
+    }
+
+    fn draw(node: RefMut<Self>) {
+        let angle = node.body.angle + node.aim_angle();
+
+        node.sprite.draw(node.body.pos, node.body.facing, angle);
+
+        if let Some(muzzle_flash_sprite) = &node.muzzle_flash_sprite {
+            if node.muzzle_flash {
+                muzzle_flash_sprite.draw(node.body.pos, node.body.facing, angle);
+            }
+        }
+
+        if !node.throwable.thrown() {
+            node.ammo.draw_hud(node.body.pos);
+        }
+    }
+}
+
+impl Gun {
+    pub fn spawn(desc: &GunDesc, pos: Vec2) -> HandleUntyped {
+        let desc = desc.clone();
+
+        let mut resources = storage::get_mut::<Resources>();
+        let texture = resources.items_textures[&format!("{}/gun", desc.name)];
+        let animation = |name: &str, desc: &AnimationDesc| Animation {
+            name: name.to_string(),
+            row: desc.row,
+            frames: desc.frames,
+            fps: desc.fps,
+        };
+        let (width, height) = desc.sprite_size;
+
+        let sprite = GunlikeAnimation::new(
+            SimulatedSprite::new(
+                width,
+                height,
+                &[
+                    animation("idle", &desc.idle),
+                    animation("shoot", &desc.shoot),
+                ],
+                false,
+            ),
+            texture,
+            desc.collider.0,
+        );
+
+        let muzzle_flash_sprite = if desc.muzzle_flash.frames != 0 {
+            Some(GunlikeAnimation::new(
+                SimulatedSprite::new(
+                    width,
+                    height,
+                    &[animation("shoot", &desc.muzzle_flash)],
+                    false,
+                ),
+                texture,
+                desc.collider.0,
+            ))
+        } else {
+            None
+        };
+
+        let body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(desc.collider.0, desc.collider.1),
+        );
+
+        let ammo = Ammo::new(
+            desc.bullets,
+            AmmoHud::from_name(&desc.hud),
+            WhenEmpty::from_name(&desc.when_empty),
+        );
+
+        scene::add_node(Gun {
+            ammo,
+            desc,
+            sprite,
+            muzzle_flash_sprite,
+            muzzle_flash: false,
+            aim: None,
+            body,
+            throwable: ThrowableItem::default(),
+        })
+        .untyped()
+    }
+
+    pub fn throw(&mut self, force: bool) {
+        self.throwable.throw(&mut self.body, force);
+        self.aim = None;
+
+        if force {
+            self.play_sound("throw");
+        }
+    }
+
+    /// Where the bullets go. The gun can't aim behind the fish,
+    /// so the aim is mirrored to the side the fish is facing
+    fn aim_direction(&self) -> Vec2 {
+        let facing = self.body.facing_dir();
+
+        match self.aim {
+            Some(aim) => {
+                let direction = aim.direction();
+                vec2(direction.x.abs() * facing.x, direction.y)
+            }
+            None => facing,
+        }
+    }
+
+    /// Sprite rotation for the aim, only for drawing
+    fn aim_angle(&self) -> f32 {
+        if self.aim.is_none() {
+            return 0.;
+        }
+
+        let direction = self.aim_direction();
+        direction.y.atan2(direction.x.abs()) * self.body.facing_dir().x
+    }
+
+    fn play_sound(&self, id: &str) {
+        let resources = storage::get::<Resources>();
+        if let Some(effects) = &resources.effects {
+            effects.play_item_sound(&self.desc.name, id);
+        }
+    }
+
+    pub fn shoot(node: Handle<Gun>, player: Handle<Player>, frame: u32) -> bool {
+        let node = &mut *scene::get_node(node);
+        let frame_duration = simulation::frames(node.desc.shoot_frame_time);
+
+        if frame == 0 {
+            if node.ammo.is_empty() {
+                node.play_sound("empty");
+
+                return true;
+            }
+
+            let player = &mut *scene::get_node(player);
+            let desc = &node.desc;
+
+            let direction = node.aim_direction();
+            let muzzle = node.body.pos
+                + vec2(desc.muzzle_offset.0, desc.muzzle_offset.1)
+                + direction * desc.muzzle_distance;
+
+            let mut resources = storage::get_mut::<Resources>();
+            if let Some(effects) = resources.effects.as_mut() {
+                effects.play_item_sound(&desc.name, "shoot");
+
+                if let Some(fx) = effects.items_fxses.get_mut(&format!("{}/shoot", desc.name)) {
+                    fx.spawn(muzzle);
+                }
+            }
+
+            GunBullet::spawn(desc, muzzle, direction, player.id);
+            player.body.speed.x = -desc.throwback * direction.x;
+
+            node.muzzle_flash = true;
+            node.sprite.set_animation(1);
+        }
+
+        if frame < node.desc.shoot.frames * frame_duration {
+            let i = frame / frame_duration;
+            node.sprite.set_frame(i);
+            if let Some(muzzle_flash_sprite) = node.muzzle_flash_sprite.as_mut() {
+                muzzle_flash_sprite.set_frame(i);
+            }
+
+            return false;
+        }
+
+        node.sprite.set_animation(0);
+        node.muzzle_flash = false;
+        node.ammo.take();
+
+        if let Some(force) = node.ammo.throw_away() {
+            node.throw(force);
+            scene::get_node(player).weapon = None;
+        }
+
+        true
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        node.sprite.update();
+        if let Some(muzzle_flash_sprite) = node.muzzle_flash_sprite.as_mut() {
+            muzzle_flash_sprite.update();
+        }
+        node.throwable.update(&mut node.body, true);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+            Gun::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+
+            node.sprite.save(writer);
+            if let Some(muzzle_flash_sprite) = &node.muzzle_flash_sprite {
+                muzzle_flash_sprite.save(writer);
+            }
+            writer.write(&node.muzzle_flash);
+            writer.write(&node.aim);
+            node.ammo.save(writer);
+            node.body.save(writer);
+            node.throwable.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+
+            node.sprite.load(reader);
+            if let Some(muzzle_flash_sprite) = node.muzzle_flash_sprite.as_mut() {
+                muzzle_flash_sprite.load(reader);
+            }
+            node.muzzle_flash = reader.read();
+            node.aim = reader.read();
+            node.ammo.load(reader);
+            node.body.load(reader);
+            node.throwable.load(reader);
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
+    pub fn weapon_capabilities() -> capabilities::Weapon {
+        fn throw(node: HandleUntyped, force: bool) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Gun>();
+
+            Gun::throw(&mut node, force);
+        }
+
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Gun>()
+                .handle();
+
+            Gun::shoot(node, player, frame)
+        }
+
+        fn is_thrown(node: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(node).unwrap().to_typed::<Gun>();
+
+            node.throwable.thrown()
+        }
+
+        fn pick_up(node: HandleUntyped, owner: Handle<Player>) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Gun>();
+
+            node.body.angle = 0.;
+            node.throwable.owner = Some(owner);
+            node.play_sound("reload");
+        }
+
+        fn mount(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Gun>();
+            let (x, y) = if node.body.facing {
+                node.desc.mount_offset
+            } else {
+                node.desc.mount_offset_left
+            };
+
+            node.body.pos = parent_pos + vec2(x, y);
+            node.body.facing = parent_facing;
+        }
+
+        fn aim(node: HandleUntyped, aim: Option<Aim>) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Gun>();
+
+            node.aim = aim;
+        }
+
+        fn collider(node: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(node).unwrap().to_typed::<Gun>();
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.desc.collider.0,
+                node.desc.collider.1,
+            )
+        }
+
+        capabilities::Weapon {
+            collider,
+            mount,
+            aim,
+            is_thrown,
+            pick_up,
+            throw,
+            shoot,
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(handle: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+
+            node.throwable.owner.is_none()
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Gun>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
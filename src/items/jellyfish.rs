@@ -0,0 +1,310 @@
+//! A jellyfish to hold on to: the fish falls slowly while holding it,
+//! and "shoot" flaps it up in the air, few times before landing
+
+use macroquad::{
+    experimental::{
+        animation::Animation,
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::{Ammo, AmmoHud, PhysicsBody, SimulatedSprite, ThrowableItem, WhenEmpty},
+    nodes::Player,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub struct Jellyfish {
+    icon: Texture2D,
+    /// Shown while mounted
+    flappy_sprite: SimulatedSprite,
+    flappy_texture: Texture2D,
+    /// Flaps in the air, restored on landing
+    flaps: Ammo,
+
+    pub body: PhysicsBody,
+    pub throwable: ThrowableItem,
+}
+
+impl Jellyfish {
+    pub const WIDTH: f32 = 32.;
+    pub const HEIGHT: f32 = 29.;
+    const FLAPPY_WIDTH: f32 = 50.;
+    const FLAPPY_HEIGHT: f32 = 51.;
+    /// Fall speed limit for the holder
+    const GLIDE_SPEED: f32 = 150.;
+    const FLAP_SPEED: f32 = 600.;
+    const FLAPS: i32 = 3;
+
+    pub fn spawn(pos: Vec2) -> HandleUntyped {
+        let mut resources = storage::get_mut::<Resources>();
+
+        let flappy_sprite = SimulatedSprite::new(
+            Self::FLAPPY_WIDTH as u32,
+            Self::FLAPPY_HEIGHT as u32,
+            &[Animation {
+                name: "flap".to_string(),
+                row: 0,
+                frames: 8,
+                fps: 10,
+            }],
+            true,
+        );
+
+        let icon = resources.items_textures["jellyfish/jellyfish"];
+        let flappy_texture = resources.items_textures["jellyfish/flappy"];
+        let body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::WIDTH, Self::HEIGHT),
+        );
+
+        scene::add_node(Jellyfish {
+            icon,
+            flappy_sprite,
+            flappy_texture,
+            flaps: Ammo::new(Self::FLAPS, AmmoHud::Bar, WhenEmpty::Keep),
+            body,
+            throwable: ThrowableItem::default(),
+        })
+        .untyped()
+    }
+
+    pub fn throw(&mut self, force: bool) {
+        self.throwable.throw(&mut self.body, force);
+    }
+
+    pub fn shoot(node: Handle<Jellyfish>, player: Handle<Player>, _frame: u32) -> bool {
+        let node = &mut *scene::get_node(node);
+        let player = &mut *scene::get_node(player);
+
+        if !player.body.on_ground && node.flaps.take() {
+            player.body.speed.y = -Self::FLAP_SPEED;
+            node.flappy_sprite.set_frame(0);
+        }
+
+        true
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        match node.throwable.owner {
+            Some(owner) => {
+                node.flappy_sprite.update();
+
+                let mut player = scene::get_node(owner);
+                if player.body.on_ground {
+                    node.flaps.reload();
+                }
+                if player.body.speed.y > Self::GLIDE_SPEED {
+                    player.body.speed.y = Self::GLIDE_SPEED;
+                }
+            }
+            None => node.throwable.update(&mut node.body, true),
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+            Jellyfish::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            node.flappy_sprite.save(writer);
+            node.flaps.save(writer);
+            node.body.save(writer);
+            node.throwable.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            node.flappy_sprite.load(reader);
+            node.flaps.load(reader);
+            node.body.load(reader);
+            node.throwable.load(reader);
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
+    fn weapon_capabilities() -> capabilities::Weapon {
+        fn throw(node: HandleUntyped, force: bool) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            Jellyfish::throw(&mut node, force);
+        }
+
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Jellyfish>()
+                .handle();
+
+            Jellyfish::shoot(node, player, frame)
+        }
+
+        fn is_thrown(node: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            node.throwable.thrown()
+        }
+
+        fn pick_up(node: HandleUntyped, owner: Handle<Player>) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            node.body.angle = 0.;
+            node.throwable.owner = Some(owner);
+        }
+
+        /// The jellyfish is above the fish head, the fish is hanging on it
+        fn mount(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool) {
+            let mut node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            node.body.pos = parent_pos + vec2(10., -30.);
+            node.body.facing = parent_facing;
+        }
+
+        fn collider(node: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                Jellyfish::WIDTH,
+                Jellyfish::HEIGHT,
+            )
+        }
+
+        capabilities::Weapon {
+            collider,
+            mount,
+            aim: capabilities::Weapon::no_aim,
+            is_thrown,
+            pick_up,
+            throw,
+            shoot,
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(handle: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            node.throwable.owner.is_none()
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Jellyfish>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
+
+impl scene::Node for Jellyfish {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::weapon_capabilities());
+        node.provides(Self::physics_capabilities());
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if node.throwable.thrown() {
+            draw_texture_ex(
+                node.icon,
+                node.body.pos.x,
+                node.body.pos.y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(Self::WIDTH, Self::HEIGHT)),
+                    flip_x: !node.body.facing,
+                    rotation: node.body.angle,
+                    ..Default::default()
+                },
+            );
+        } else {
+            // the flappy one is bigger, keep it centered over the small one
+            let offset = vec2(
+                Self::FLAPPY_WIDTH - Self::WIDTH,
+                Self::FLAPPY_HEIGHT - Self::HEIGHT,
+            ) / 2.;
+
+            draw_texture_ex(
+                node.flappy_texture,
+                node.body.pos.x - offset.x,
+                node.body.pos.y - offset.y,
+                WHITE,
+                DrawTextureParams {
+                    source: Some(node.flappy_sprite.frame().source_rect),
+                    dest_size: Some(node.flappy_sprite.frame().dest_size),
+                    flip_x: !node.body.facing,
+                    ..Default::default()
+                },
+            );
+
+            node.flaps.draw_hud(node.body.pos - offset);
+        }
+    }
+}
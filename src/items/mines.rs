@@ -0,0 +1,388 @@
+//! Mines: "shoot" puts a mine under the fish, after a moment it is armed and
+//! explodes when anybody steps on it, the one who placed it included
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::{Ammo, AmmoHud, PhysicsBody, ThrowableItem, WhenEmpty},
+    items::explosive,
+    nodes::Player,
+    simulation::FRAME_TIME,
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+const WIDTH: f32 = 30.;
+const HEIGHT: f32 = 15.;
+
+fn play_sound(id: &str) {
+    let resources = storage::get::<Resources>();
+    if let Some(effects) = &resources.effects {
+        effects.play_item_sound("mines", id);
+    }
+}
+
+/// The texture has two frames: a mine as it is, and an armed one with a red light
+fn draw_mine(texture: Texture2D, pos: Vec2, armed: bool, params: DrawTextureParams) {
+    let frame = if armed { 1. } else { 0. };
+
+    draw_texture_ex(
+        texture,
+        pos.x,
+        pos.y,
+        WHITE,
+        DrawTextureParams {
+            source: Some(Rect::new(WIDTH * frame, 0., WIDTH, HEIGHT)),
+            dest_size: Some(vec2(WIDTH, HEIGHT)),
+            ..params
+        },
+    );
+}
+
+pub struct ArmedMine {
+    body: PhysicsBody,
+    owner: u8,
+    lived: f32,
+    texture: Texture2D,
+}
+
+impl ArmedMine {
+    /// Time to run away after placing a mine
+    const ARMING_TIME: f32 = 1.;
+    const EXPLOSION_RADIUS: f32 = 50.;
+
+    pub fn spawn(pos: Vec2, owner: u8) -> HandleUntyped {
+        capabilities::add_simulated_node(
+            ArmedMine {
+                body: Self::body(pos),
+                owner,
+                lived: 0.,
+                texture: storage::get::<Resources>().items_textures["mines/mine"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn body(pos: Vec2) -> PhysicsBody {
+        let mut resources = storage::get_mut::<Resources>();
+        PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(WIDTH, HEIGHT),
+        )
+    }
+
+    fn armed(&self) -> bool {
+        self.lived > Self::ARMING_TIME
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.body.update();
+        node.lived += FRAME_TIME;
+
+        if !node.armed() {
+            return;
+        }
+
+        let hitbox = Rect::new(node.body.pos.x, node.body.pos.y, WIDTH, HEIGHT);
+        let stepped_on = scene::find_nodes_by_type::<Player>()
+            .any(|player| !player.dead && hitbox.overlaps(&player.get_hitbox()));
+
+        if stepped_on {
+            let center = node.body.pos + node.body.size / 2.;
+            explosive::explode(center, Self::EXPLOSION_RADIUS, node.owner);
+            play_sound("explode");
+
+            node.delete();
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ArmedMine>();
+            ArmedMine::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ArmedMine>();
+
+            node.body.save(writer);
+            writer.write(&node.owner);
+            writer.write(&node.lived);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ArmedMine>();
+
+            node.body.load(reader);
+            node.owner = reader.read();
+            node.lived = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            ArmedMine::spawn(vec2(0., 0.), 0)
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for ArmedMine {
+    fn draw(node: RefMut<Self>) {
+        draw_mine(
+            node.texture,
+            node.body.pos,
+            node.armed(),
+            DrawTextureParams::default(),
+        );
+    }
+}
+
+pub struct Mines {
+    texture: Texture2D,
+
+    pub ammo: Ammo,
+
+    pub body: PhysicsBody,
+    pub throwable: ThrowableItem,
+}
+
+impl Mines {
+    pub const MINES: i32 = 3;
+
+    pub fn spawn(pos: Vec2) -> HandleUntyped {
+        let mut resources = storage::get_mut::<Resources>();
+
+        let texture = resources.items_textures["mines/mine"];
+        let body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(WIDTH, HEIGHT),
+        );
+
+        scene::add_node(Mines {
+            texture,
+            ammo: Ammo::new(Self::MINES, AmmoHud::Circles, WhenEmpty::Drop),
+            body,
+            throwable: ThrowableItem::default(),
+        })
+        .untyped()
+    }
+
+    pub fn throw(&mut self, force: bool) {
+        self.throwable.throw(&mut self.body, force);
+
+        if force {
+            play_sound("throw");
+        }
+    }
+
+    pub fn shoot(node: Handle<Mines>, player: Handle<Player>, _frame: u32) -> bool {
+        let node = &mut *scene::get_node(node);
+        let player = &mut *scene::get_node(player);
+
+        if node.ammo.is_empty() {
+            play_sound("empty");
+        } else {
+            node.ammo.take();
+
+            // right under the fish, falling down if the fish is in the air
+            let pos = player.body.pos
+                + vec2(
+                    (player.body.size.x - WIDTH) / 2.,
+                    player.body.size.y - HEIGHT,
+                );
+            ArmedMine::spawn(pos, player.id);
+
+            play_sound("set");
+        }
+
+        if let Some(force) = node.ammo.throw_away() {
+            node.throw(force);
+            player.weapon = None;
+        }
+
+        true
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        node.throwable.update(&mut node.body, true);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+            Mines::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+
+            node.ammo.save(writer);
+            node.body.save(writer);
+            node.throwable.save(writer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+
+            node.ammo.load(reader);
+            node.body.load(reader);
+            node.throwable.load(reader);
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
+    fn weapon_capabilities() -> capabilities::Weapon {
+        fn throw(node: HandleUntyped, force: bool) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Mines>();
+
+            Mines::throw(&mut node, force);
+        }
+
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
+            let node = scene::get_untyped_node(node)
+                .unwrap()
+                .to_typed::<Mines>()
+                .handle();
+
+            Mines::shoot(node, player, frame)
+        }
+
+        fn is_thrown(node: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(node).unwrap().to_typed::<Mines>();
+
+            node.throwable.thrown()
+        }
+
+        fn pick_up(node: HandleUntyped, owner: Handle<Player>) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Mines>();
+
+            node.body.angle = 0.;
+            node.throwable.owner = Some(owner);
+        }
+
+        fn mount(node: HandleUntyped, parent_pos: Vec2, parent_facing: bool) {
+            let mut node = scene::get_untyped_node(node).unwrap().to_typed::<Mines>();
+
+            let mount_pos = if parent_facing {
+                vec2(20., 24.)
+            } else {
+                vec2(26., 24.)
+            };
+
+            node.body.pos = parent_pos + mount_pos;
+            node.body.facing = parent_facing;
+        }
+
+        fn collider(node: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(node).unwrap().to_typed::<Mines>();
+
+            Rect::new(node.body.pos.x, node.body.pos.y, WIDTH, HEIGHT)
+        }
+
+        capabilities::Weapon {
+            collider,
+            mount,
+            aim: capabilities::Weapon::no_aim,
+            is_thrown,
+            pick_up,
+            throw,
+            shoot,
+        }
+    }
+
+    fn physics_capabilities() -> capabilities::PhysicsObject {
+        fn active(handle: HandleUntyped) -> bool {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+
+            node.throwable.owner.is_none()
+        }
+        fn collider(handle: HandleUntyped) -> Rect {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+
+            Rect::new(
+                node.body.pos.x,
+                node.body.pos.y,
+                node.body.size.x,
+                node.body.size.y,
+            )
+        }
+        fn set_speed_x(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+            node.body.speed.x = speed;
+        }
+        fn set_speed_y(handle: HandleUntyped, speed: f32) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Mines>();
+            node.body.speed.y = speed;
+        }
+
+        capabilities::PhysicsObject {
+            active,
+            collider,
+            set_speed_x,
+            set_speed_y,
+        }
+    }
+}
+
+impl scene::Node for Mines {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::weapon_capabilities());
+        node.provides(Self::physics_capabilities());
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
+    }
+
+    fn draw(node: RefMut<Self>) {
+        draw_mine(
+            node.texture,
+            node.body.pos,
+            false,
+            DrawTextureParams {
+                flip_x: !node.body.facing,
+                rotation: node.body.angle,
+                ..Default::default()
+            },
+        );
+
+        if !node.throwable.thrown() {
+            node.ammo.draw_hud(node.body.pos);
+        }
+    }
+}
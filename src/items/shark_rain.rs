@@ -0,0 +1,147 @@
+//! A bunch of sharks falling from the top of the map at random horizontal positions
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    items::environmental::{self, Summon},
+    simulation::{self, FRAME_TIME},
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub const SHARK_RAIN: Summon = Summon {
+    tiled_name: "shark",
+    size: (32., 34.),
+    summon: SharkRain::spawn,
+};
+
+/// All the sharks of one rain are in one node
+pub struct SharkRain {
+    sharks: Vec<Vec2>,
+    owner: u8,
+    texture: Texture2D,
+}
+
+impl SharkRain {
+    const WIDTH: f32 = 60.;
+    const HEIGHT: f32 = 220.;
+    const SHARKS: usize = 7;
+    const SPEED: f32 = 600.;
+    /// Sharks are not falling all at once, the last one is that much higher
+    const SPREAD_HEIGHT: f32 = 600.;
+    const HITBOX_INSET: f32 = 10.;
+
+    pub fn spawn(owner: u8, _pos: Vec2) -> HandleUntyped {
+        let map_width = environmental::map_size().x;
+
+        let sharks = (0..Self::SHARKS)
+            .map(|_| {
+                vec2(
+                    simulation::gen_range(0., map_width - Self::WIDTH),
+                    -Self::HEIGHT - simulation::gen_range(0., Self::SPREAD_HEIGHT),
+                )
+            })
+            .collect();
+
+        capabilities::add_simulated_node(
+            SharkRain {
+                sharks,
+                owner,
+                texture: storage::get::<Resources>().items_textures["shark/shark"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let map_height = environmental::map_size().y;
+        let owner = node.owner;
+
+        for shark in &mut node.sharks {
+            shark.y += Self::SPEED * FRAME_TIME;
+
+            let hitbox = Rect::new(
+                shark.x + Self::HITBOX_INSET,
+                shark.y + Self::HITBOX_INSET,
+                Self::WIDTH - Self::HITBOX_INSET * 2.,
+                Self::HEIGHT - Self::HITBOX_INSET * 2.,
+            );
+            environmental::kill_in_rect(hitbox, owner);
+        }
+
+        node.sharks.retain(|shark| shark.y < map_height);
+        if node.sharks.is_empty() {
+            node.delete();
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<SharkRain>();
+            SharkRain::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<SharkRain>();
+
+            writer.write(&node.sharks.len());
+            for shark in &node.sharks {
+                writer.write_vec2(*shark);
+            }
+            writer.write(&node.owner);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<SharkRain>();
+
+            let count: usize = reader.read();
+            node.sharks = (0..count).map(|_| reader.read_vec2()).collect();
+            node.owner = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            SharkRain::spawn(0, vec2(0., 0.))
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for SharkRain {
+    fn draw(node: RefMut<Self>) {
+        for shark in &node.sharks {
+            draw_texture_ex(
+                node.texture,
+                shark.x,
+                shark.y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(Self::WIDTH, Self::HEIGHT)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
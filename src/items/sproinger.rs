@@ -2,26 +2,29 @@ use macroquad::{
     audio::play_sound_once,
     color,
     experimental::{
-        animation::{AnimatedSprite, Animation},
+        animation::Animation,
         collections::storage,
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
         scene::{self, HandleUntyped, RefMut},
-        state_machine::{State, StateMachine},
     },
     prelude::*,
 };
 
 use crate::{
-    capabilities::{NetworkReplicate, PhysicsObject},
+    capabilities::{NetworkReplicate, PhysicsObject, Snapshot},
+    components::SimulatedSprite,
+    simulation,
+    snapshot::{SnapshotReader, SnapshotWriter},
     Resources,
 };
 
 use crate::capabilities::PhysicsObjectTrait;
 
 pub struct Sproinger {
-    sprite: AnimatedSprite,
+    sprite: SimulatedSprite,
     pos: Vec2,
-    state_machine: StateMachine<Self>,
+    state: usize,
+    /// Simulation frames spent in the current state
+    state_frames: u32,
 }
 
 impl Sproinger {
@@ -29,11 +32,11 @@ impl Sproinger {
     pub const TRIGGER_HEIGHT: f32 = 8.0;
     pub const FORCE: f32 = 1100.0;
 
-    const ST_NORMAL: usize = 0;
-    const ST_JUMP: usize = 1;
+    pub const ST_NORMAL: usize = 0;
+    pub const ST_JUMP: usize = 1;
 
     pub fn spawn(pos: Vec2) -> HandleUntyped {
-        let sprite = AnimatedSprite::new(
+        let sprite = SimulatedSprite::new(
             31,
             20,
             &[
@@ -59,25 +62,27 @@ impl Sproinger {
             false,
         );
 
-        let mut state_machine = StateMachine::new();
-
-        state_machine.add_state(Self::ST_NORMAL, State::new().update(Self::update_normal));
-        state_machine.add_state(
-            Self::ST_JUMP,
-            State::new()
-                .update(Self::update_jump)
-                .coroutine(Self::jump_coroutine),
-        );
-
         scene::add_node(Sproinger {
             sprite,
             pos,
-            state_machine,
+            state: Self::ST_NORMAL,
+            state_frames: 0,
         })
         .untyped()
     }
 
-    pub fn update_normal(node: &mut RefMut<Self>, _dt: f32) {
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    fn set_state(&mut self, state: usize) {
+        if self.state != state {
+            self.state = state;
+            self.state_frames = 0;
+        }
+    }
+
+    pub fn update_normal(node: &mut RefMut<Self>) {
         let sproinger_rect = Rect::new(
             node.pos.x, // - (Self::TRIGGER_WIDTH / 2.0),
             node.pos.y + (node.sprite.frame().dest_size.y - Self::TRIGGER_HEIGHT),
@@ -90,44 +95,30 @@ impl Sproinger {
             let intersect = sproinger_rect.intersect(object_collider);
             if intersect.is_some() {
                 let resources = storage::get_mut::<Resources>();
-                play_sound_once(resources.jump_sound);
+                if let Some(effects) = &resources.effects {
+                    play_sound_once(effects.jump_sound);
+                }
 
                 physics_object.set_speed_y(-Self::FORCE);
 
-                node.state_machine.set_state(Self::ST_JUMP);
+                node.set_state(Self::ST_JUMP);
             }
         }
     }
 
-    pub fn update_jump(_node: &mut RefMut<Self>, _dt: f32) {}
+    pub fn update_jump(node: &mut RefMut<Self>, frame: u32) {
+        let frame_duration = simulation::frames(0.08);
 
-    fn jump_coroutine(node: &mut RefMut<Self>) -> Coroutine {
-        let node_handle = node.handle();
-
-        let coroutine = async move {
-            {
-                let mut node = scene::get_node(node_handle);
-                node.sprite.set_animation(2);
-            }
-            for i in 0..2 {
-                {
-                    let mut node = scene::get_node(node_handle);
-                    if node.sprite.current_animation() != 2 {
-                        return;
-                    }
-                    node.sprite.set_frame(i);
-                }
-                wait_seconds(0.08).await;
-            }
-            wait_seconds(0.5).await;
+        if frame == 0 {
+            node.sprite.set_animation(2);
+        }
 
-            {
-                let mut node = scene::get_node(node_handle);
-                node.sprite.set_animation(0);
-                node.state_machine.set_state(Self::ST_NORMAL);
-            }
-        };
-        start_coroutine(coroutine)
+        if frame < 2 * frame_duration {
+            node.sprite.set_frame(frame / frame_duration);
+        } else if frame >= 2 * frame_duration + simulation::frames(0.5) {
+            node.sprite.set_animation(0);
+            node.set_state(Self::ST_NORMAL);
+        }
     }
 }
 
@@ -143,17 +134,54 @@ impl Sproinger {
         NetworkReplicate { network_update }
     }
 
-    fn network_update(node: RefMut<Self>) {
-        StateMachine::update_detached(node, |node| &mut node.state_machine);
+    fn snapshot_capabilities() -> Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Sproinger>();
+
+            node.sprite.save(writer);
+            writer.write(&(node.state as u32));
+            writer.write(&node.state_frames);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Sproinger>();
+
+            node.sprite.load(reader);
+            node.state = reader.read::<u32>() as usize;
+            node.state_frames = reader.read();
+        }
+
+        Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let frame = node.state_frames;
+        node.state_frames += 1;
+
+        match node.state {
+            Self::ST_NORMAL => Self::update_normal(&mut node),
+            Self::ST_JUMP => Self::update_jump(&mut node, frame),
+            _ => unreachable!(),
+        }
+
+        node.sprite.update();
     }
 }
 impl scene::Node for Sproinger {
     fn ready(mut node: RefMut<Self>) {
         node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
     }
 
-    fn draw(mut node: RefMut<Self>) {
-        node.sprite.update();
+    fn draw(node: RefMut<Self>) {
         let resources = storage::get_mut::<Resources>();
 
         draw_texture_ex(
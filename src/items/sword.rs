@@ -1,10 +1,8 @@
 use macroquad::{
-    audio::play_sound_once,
     color,
     experimental::{
-        animation::{AnimatedSprite, Animation},
+        animation::Animation,
         collections::storage,
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
         scene::{self, Handle, HandleUntyped, RefMut},
     },
     prelude::*,
@@ -12,13 +10,15 @@ use macroquad::{
 
 use crate::{
     capabilities,
-    components::{PhysicsBody, ThrowableItem},
+    components::{PhysicsBody, SimulatedSprite, ThrowableItem},
     nodes::Player,
+    simulation,
+    snapshot::{SnapshotReader, SnapshotWriter},
     Resources,
 };
 
 pub struct Sword {
-    sprite: AnimatedSprite,
+    sprite: SimulatedSprite,
     body: PhysicsBody,
     throwable: ThrowableItem,
     origin_pos: Vec2,
@@ -33,6 +33,8 @@ impl scene::Node for Sword {
     fn ready(mut node: RefMut<Self>) {
         node.provides(Self::gun_capabilities());
         node.provides(Self::physics_capabilities());
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
     }
 
     fn draw(sword: RefMut<Self>) {
@@ -92,55 +94,6 @@ impl scene::Node for Sword {
         //     RED,
         // );
     }
-
-    fn fixed_update(mut node: RefMut<Self>) {
-        node.sprite.update();
-
-        let map_bottom = {
-            let resources = storage::get::<Resources>();
-
-            resources.tiled_map.raw_tiled_map.tileheight * resources.tiled_map.raw_tiled_map.height
-        } as f32;
-
-        // respawn sword
-        // should not be here, just a hack for swordthrow loc
-        if node.body.pos.y > map_bottom {
-            node.body.pos = node.spawn_pos.0;
-            node.body.facing = node.spawn_pos.1;
-            node.body.speed = vec2(0., 0.);
-            node.deadly_dangerous = false;
-            node.throw(false);
-            return;
-        }
-
-        let node = &mut *node;
-        node.throwable.update(&mut node.body, false);
-
-        if node.throwable.thrown() {
-            if (node.origin_pos - node.body.pos).length() > 70. {
-                node.deadly_dangerous = true;
-            }
-            if node.body.speed.length() <= 200.0 {
-                node.deadly_dangerous = false;
-            }
-            if node.body.on_ground && node.body.speed.length() <= 400.0 {
-                node.deadly_dangerous = false;
-            }
-
-            if node.deadly_dangerous {
-                let others = scene::find_nodes_by_type::<crate::nodes::Player>();
-                let sword_hit_box = Rect::new(node.body.pos.x - 10., node.body.pos.y, 60., 30.);
-
-                for mut other in others {
-                    if Rect::new(other.body.pos.x, other.body.pos.y, 20., 64.)
-                        .overlaps(&sword_hit_box)
-                    {
-                        other.kill(!node.body.facing);
-                    }
-                }
-            }
-        }
-    }
 }
 
 impl Sword {
@@ -148,7 +101,7 @@ impl Sword {
     pub const COLLIDER_HEIGHT: f32 = 32.0;
 
     pub fn spawn(pos: Vec2) -> HandleUntyped {
-        let sprite = AnimatedSprite::new(
+        let sprite = SimulatedSprite::new(
             65,
             93,
             &[
@@ -190,58 +143,56 @@ impl Sword {
         self.throwable.throw(&mut self.body, force);
 
         self.origin_pos = self.body.pos;
+
+        if force {
+            let resources = storage::get::<Resources>();
+            if let Some(effects) = &resources.effects {
+                effects.play_item_sound("sword", "throw");
+            }
+        }
     }
 
-    pub fn shoot(node: Handle<Sword>, player: Handle<Player>) -> Coroutine {
-        let coroutine = async move {
+    pub fn shoot(node: Handle<Sword>, player: Handle<Player>, frame: u32) -> bool {
+        let frame_duration = simulation::frames(0.08);
+
+        if frame == 0 {
             {
                 let resources = storage::get_mut::<Resources>();
-                play_sound_once(resources.sword_sound);
+                if let Some(effects) = &resources.effects {
+                    effects.play_item_sound("sword", "shoot");
+                }
 
                 let sword = &mut *scene::get_node(node);
                 sword.sprite.set_animation(1);
             }
 
-            {
-                let player = &mut *scene::get_node(player);
-                let others = scene::find_nodes_by_type::<crate::nodes::Player>();
-                let sword_hit_box = if player.body.facing {
-                    Rect::new(player.body.pos.x + 35., player.body.pos.y - 5., 40., 60.)
-                } else {
-                    Rect::new(player.body.pos.x - 50., player.body.pos.y - 5., 40., 60.)
-                };
-
-                for mut other in others {
-                    if Rect::new(other.body.pos.x, other.body.pos.y, 20., 64.)
-                        .overlaps(&sword_hit_box)
-                    {
-                        scene::find_node_by_type::<crate::nodes::Camera>()
-                            .unwrap()
-                            .shake_noise(2., 6, 1.0);
-                        other.kill(!player.body.facing);
-                    }
-                }
-            }
+            let player = &mut *scene::get_node(player);
+            let others = scene::find_nodes_by_type::<crate::nodes::Player>();
+            let sword_hit_box = if player.body.facing {
+                Rect::new(player.body.pos.x + 35., player.body.pos.y - 5., 40., 60.)
+            } else {
+                Rect::new(player.body.pos.x - 50., player.body.pos.y - 5., 40., 60.)
+            };
 
-            for i in 0u32..3 {
+            for mut other in others {
+                if Rect::new(other.body.pos.x, other.body.pos.y, 20., 64.).overlaps(&sword_hit_box)
                 {
-                    let sword = &mut *scene::get_node(node);
-                    sword.sprite.set_frame(i);
+                    scene::find_node_by_type::<crate::nodes::Camera>()
+                        .unwrap()
+                        .shake_noise(2., 6, 1.0);
+                    other.kill(!player.body.facing, Some(player.id));
                 }
-
-                wait_seconds(0.08).await;
-            }
-
-            {
-                let mut sword = scene::get_node(node);
-                sword.sprite.set_animation(0);
             }
+        }
 
-            let player = &mut *scene::get_node(player);
-            player.state_machine.set_state(Player::ST_NORMAL);
-        };
-
-        start_coroutine(coroutine)
+        let sword = &mut *scene::get_node(node);
+        if frame < 3 * frame_duration {
+            sword.sprite.set_frame(frame / frame_duration);
+            false
+        } else {
+            sword.sprite.set_animation(0);
+            true
+        }
     }
 
     pub fn gun_capabilities() -> capabilities::Weapon {
@@ -251,13 +202,13 @@ impl Sword {
             Sword::throw(&mut *node, force);
         }
 
-        fn shoot(node: HandleUntyped, player: Handle<Player>) -> Coroutine {
+        fn shoot(node: HandleUntyped, player: Handle<Player>, frame: u32) -> bool {
             let node = scene::get_untyped_node(node)
                 .unwrap()
                 .to_typed::<Sword>()
                 .handle();
 
-            Sword::shoot(node, player)
+            Sword::shoot(node, player, frame)
         }
 
         fn is_thrown(node: HandleUntyped) -> bool {
@@ -300,6 +251,7 @@ impl Sword {
         capabilities::Weapon {
             collider,
             mount,
+            aim: capabilities::Weapon::no_aim,
             is_thrown,
             pick_up,
             throw,
@@ -307,6 +259,93 @@ impl Sword {
         }
     }
 
+    fn network_update(mut node: RefMut<Self>) {
+        node.sprite.update();
+
+        let map_bottom = {
+            let resources = storage::get::<Resources>();
+
+            resources.tiled_map.raw_tiled_map.tileheight * resources.tiled_map.raw_tiled_map.height
+        } as f32;
+
+        // respawn sword
+        // should not be here, just a hack for swordthrow loc
+        if node.body.pos.y > map_bottom {
+            node.body.pos = node.spawn_pos.0;
+            node.body.facing = node.spawn_pos.1;
+            node.body.speed = vec2(0., 0.);
+            node.deadly_dangerous = false;
+            node.throw(false);
+            return;
+        }
+
+        let node = &mut *node;
+        node.throwable.update(&mut node.body, false);
+
+        if node.throwable.thrown() {
+            if (node.origin_pos - node.body.pos).length() > 70. {
+                node.deadly_dangerous = true;
+            }
+            if node.body.speed.length() <= 200.0 {
+                node.deadly_dangerous = false;
+            }
+            if node.body.on_ground && node.body.speed.length() <= 400.0 {
+                node.deadly_dangerous = false;
+            }
+
+            if node.deadly_dangerous {
+                let others = scene::find_nodes_by_type::<crate::nodes::Player>();
+                let sword_hit_box = Rect::new(node.body.pos.x - 10., node.body.pos.y, 60., 30.);
+
+                for mut other in others {
+                    if Rect::new(other.body.pos.x, other.body.pos.y, 20., 64.)
+                        .overlaps(&sword_hit_box)
+                    {
+                        // nobody remembers who threw it
+                        other.kill(!node.body.facing, None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Sword>();
+            Sword::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle).unwrap().to_typed::<Sword>();
+
+            node.sprite.save(writer);
+            node.body.save(writer);
+            node.throwable.save(writer);
+            writer.write_vec2(node.origin_pos);
+            writer.write(&node.deadly_dangerous);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle).unwrap().to_typed::<Sword>();
+
+            node.sprite.load(reader);
+            node.body.load(reader);
+            node.throwable.load(reader);
+            node.origin_pos = reader.read_vec2();
+            node.deadly_dangerous = reader.read();
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
+
     fn physics_capabilities() -> capabilities::PhysicsObject {
         fn active(handle: HandleUntyped) -> bool {
             let node = scene::get_untyped_node(handle).unwrap().to_typed::<Sword>();
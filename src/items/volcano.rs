@@ -0,0 +1,274 @@
+//! A volcano rising from the bottom of the map and throwing lava rocks around
+//! The rocks are physics bodies, like the cannonballs: they bounce off the level
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities,
+    components::PhysicsBody,
+    items::environmental::{self, Summon},
+    simulation::{self, FRAME_TIME},
+    snapshot::{SnapshotReader, SnapshotWriter},
+    Resources,
+};
+
+pub const VOLCANO: Summon = Summon {
+    tiled_name: "volcano",
+    size: (36., 22.),
+    summon: EruptingVolcano::spawn,
+};
+
+pub struct LavaRock {
+    body: PhysicsBody,
+    owner: u8,
+    lived: f32,
+}
+
+impl LavaRock {
+    const SIZE: f32 = 12.;
+    const BOUNCYNESS: f32 = 0.5;
+    const LIFETIME: f32 = 2.5;
+
+    pub fn spawn(pos: Vec2, speed: Vec2, owner: u8) -> HandleUntyped {
+        let mut body = Self::body(pos);
+        body.speed = speed;
+
+        capabilities::add_simulated_node(
+            LavaRock {
+                body,
+                owner,
+                lived: 0.,
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    fn body(pos: Vec2) -> PhysicsBody {
+        let mut resources = storage::get_mut::<Resources>();
+        let mut body = PhysicsBody::new(
+            &mut resources.collision_world,
+            pos,
+            0.0,
+            vec2(Self::SIZE, Self::SIZE),
+        );
+        body.bouncyness = Self::BOUNCYNESS;
+        body
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.lived += FRAME_TIME;
+        if node.lived > Self::LIFETIME || node.body.pos.y > environmental::map_size().y {
+            node.delete();
+            return;
+        }
+
+        node.body.update();
+
+        let hitbox = Rect::new(node.body.pos.x, node.body.pos.y, Self::SIZE, Self::SIZE);
+        environmental::kill_in_rect(hitbox, node.owner);
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<LavaRock>();
+            LavaRock::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<LavaRock>();
+
+            node.body.save(writer);
+            writer.write(&node.owner);
+            writer.write(&node.lived);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<LavaRock>();
+
+            node.body.load(reader);
+            node.owner = reader.read();
+            node.lived = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            LavaRock::spawn(vec2(0., 0.), vec2(0., 0.), 0)
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for LavaRock {
+    fn draw(node: RefMut<Self>) {
+        let half_size = Self::SIZE / 2.;
+        draw_circle(
+            node.body.pos.x + half_size,
+            node.body.pos.y + half_size,
+            half_size,
+            Color::new(1.0, 0.45, 0.1, 1.0),
+        );
+    }
+}
+
+pub struct EruptingVolcano {
+    owner: u8,
+    lived: f32,
+    /// Time left till the next rock
+    rock_timer: f32,
+    texture: Texture2D,
+}
+
+impl EruptingVolcano {
+    const WIDTH: f32 = 395.;
+    const HEIGHT: f32 = 100.;
+    const RISE_TIME: f32 = 1.;
+    const ERUPTION_TIME: f32 = 3.;
+    const ROCK_INTERVAL: f32 = 0.15;
+
+    pub fn spawn(owner: u8, _pos: Vec2) -> HandleUntyped {
+        capabilities::add_simulated_node(
+            EruptingVolcano {
+                owner,
+                lived: 0.,
+                rock_timer: 0.,
+                texture: storage::get::<Resources>().items_textures["volcano/volcano"],
+            },
+            Self::network_capabilities(),
+            Self::snapshot_capabilities(),
+        )
+    }
+
+    /// Rising, erupting for a while and going back down
+    fn pos(&self) -> Vec2 {
+        let map_size = environmental::map_size();
+        let sink_start = Self::RISE_TIME + Self::ERUPTION_TIME;
+
+        let risen = if self.lived < Self::RISE_TIME {
+            self.lived / Self::RISE_TIME
+        } else if self.lived < sink_start {
+            1.
+        } else {
+            1. - (self.lived - sink_start) / Self::RISE_TIME
+        };
+
+        vec2(
+            (map_size.x - Self::WIDTH) / 2.,
+            map_size.y - Self::HEIGHT * risen.max(0.),
+        )
+    }
+
+    fn erupting(&self) -> bool {
+        self.lived >= Self::RISE_TIME && self.lived < Self::RISE_TIME + Self::ERUPTION_TIME
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.lived += FRAME_TIME;
+
+        if node.erupting() {
+            node.rock_timer -= FRAME_TIME;
+            if node.rock_timer <= 0. {
+                node.rock_timer = Self::ROCK_INTERVAL;
+
+                // right above the crater, a rock spawned inside the ground would be stuck there
+                let crater = node.pos() + vec2(Self::WIDTH / 2., -LavaRock::SIZE);
+                let speed = vec2(
+                    simulation::gen_range(-400., 400.),
+                    simulation::gen_range(-1300., -900.),
+                );
+                LavaRock::spawn(crater, speed, node.owner);
+            }
+        }
+
+        // the volcano itself is hot too
+        let pos = node.pos();
+        environmental::kill_in_rect(
+            Rect::new(pos.x, pos.y, Self::WIDTH, Self::HEIGHT),
+            node.owner,
+        );
+
+        if node.lived > Self::RISE_TIME * 2. + Self::ERUPTION_TIME {
+            node.delete();
+        }
+    }
+
+    fn network_capabilities() -> capabilities::NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EruptingVolcano>();
+            EruptingVolcano::network_update(node);
+        }
+
+        capabilities::NetworkReplicate { network_update }
+    }
+
+    fn snapshot_capabilities() -> capabilities::Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EruptingVolcano>();
+
+            writer.write(&node.owner);
+            writer.write(&node.lived);
+            writer.write(&node.rock_timer);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<EruptingVolcano>();
+
+            node.owner = reader.read();
+            node.lived = reader.read();
+            node.rock_timer = reader.read();
+        }
+
+        fn rebuild() -> HandleUntyped {
+            EruptingVolcano::spawn(0, vec2(0., 0.))
+        }
+
+        capabilities::Snapshot {
+            save,
+            load,
+            rebuild: Some(rebuild),
+        }
+    }
+}
+
+impl scene::Node for EruptingVolcano {
+    fn draw(node: RefMut<Self>) {
+        let pos = node.pos();
+
+        draw_texture_ex(
+            node.texture,
+            pos.x,
+            pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(Self::WIDTH, Self::HEIGHT)),
+                ..Default::default()
+            },
+        );
+    }
+}
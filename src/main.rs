@@ -11,107 +11,323 @@ use macroquad::{
 use macroquad_platformer::{Tile, World as CollisionWorld};
 use particles::EmittersCache;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
 mod capabilities;
+mod determinism;
 mod gui;
+mod headless;
 mod input;
 mod items;
 mod nodes;
 mod noise;
+mod replay;
+mod simulation;
+mod snapshot;
 
 pub mod components;
 
 pub use input::{Input, InputScheme};
 
+/// One whale colour per player
+pub const MAX_PLAYERS: usize = 4;
+
 pub enum GameType {
     Local(Vec<InputScheme>),
     Network {
-        socket: Box<dyn nodes::network::Socket>,
+        connection: Rc<nodes::network::Connection>,
         id: usize,
-        input_scheme: InputScheme,
+        mode: nodes::network::NetworkMode,
+        /// Match RNG seed, agreed with the remote peers
+        seed: u64,
+        /// One for each fish played on this peer
+        input_schemes: Vec<InputScheme>,
+        /// Amount of fishes on each peer, by peer id
+        peers_players: Vec<usize>,
     },
+    Replay(replay::Replay),
+}
+
+impl GameType {
+    fn players_count(&self) -> usize {
+        match self {
+            GameType::Local(players_input) => players_input.len(),
+            GameType::Network { peers_players, .. } => peers_players.iter().sum(),
+            GameType::Replay(replay) => replay.header.players as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LevelOrder {
+    InOrder,
+    Shuffled,
 }
 
+/// Maps for the rounds of the match, starting with "first"
+/// Shuffled with the match seed, so all the network peers get the same rotation
+fn level_rotation(levels: &[String], first: &str, order: LevelOrder, seed: u64) -> Vec<String> {
+    use simulation::RandomRange;
+
+    let first_ix = levels.iter().position(|level| level == first).unwrap_or(0);
+    let mut rotation = levels[first_ix..]
+        .iter()
+        .chain(&levels[..first_ix])
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if order == LevelOrder::Shuffled {
+        let mut rng = simulation::MatchRng::new(seed);
+        // Fisher-Yates, the first level stays first
+        for i in (2..rotation.len()).rev() {
+            let j = usize::gen_range(&mut rng, 1, i + 1);
+            rotation.swap(i, j);
+        }
+    }
+
+    rotation
+}
+
+/// Sounds and particles: the simulation triggers them, but never depends on them
+/// Not loaded in headless mode
 #[allow(dead_code)]
-struct Resources {
+struct Effects {
     hit_fxses: EmittersCache,
     explosion_fxses: EmittersCache,
     life_ui_explosion_fxses: EmittersCache,
+    jump_sound: audio::Sound,
+    pickup_sound: audio::Sound,
+    player_landing_sound: audio::Sound,
+    player_throw_sound: audio::Sound,
+    player_die_sound: audio::Sound,
+    items_fxses: HashMap<String, EmittersCache>,
+    items_sounds: HashMap<String, audio::Sound>,
+}
+
+impl Effects {
+    async fn new() -> Result<Effects, macroquad::prelude::FileError> {
+        let jump_sound = load_sound("assets/sounds/jump.wav").await?;
+        let pickup_sound = load_sound("assets/sounds/pickup.wav").await?;
+        let player_landing_sound = load_sound("assets/sounds/player_landing.wav").await?;
+        let player_throw_sound = load_sound("assets/sounds/throw_noiz.wav").await?;
+        let player_die_sound = load_sound("assets/sounds/fish_fillet.wav").await?;
+
+        const HIT_FX: &str = include_str!("../assets/fxses/hit.json");
+        const EXPLOSION_FX: &str = include_str!("../assets/fxses/explosion.json");
+        const LIFE_UI_FX: &str = include_str!("../assets/fxses/life_ui_explosion.json");
+
+        let hit_fxses = EmittersCache::new(nanoserde::DeJson::deserialize_json(HIT_FX).unwrap());
+        let explosion_fxses =
+            EmittersCache::new(nanoserde::DeJson::deserialize_json(EXPLOSION_FX).unwrap());
+        let life_ui_explosion_fxses =
+            EmittersCache::new(nanoserde::DeJson::deserialize_json(LIFE_UI_FX).unwrap());
+
+        let (fxses, sounds) = {
+            let registry = storage::get::<items::ItemRegistry>();
+            (
+                registry.resources(|item| &item.fxses),
+                registry.resources(|item| &item.sounds),
+            )
+        };
+
+        let mut items_fxses = HashMap::new();
+        for (key, path) in fxses {
+            let json = load_string(&path).await?;
+            let emitter_cache =
+                EmittersCache::new(nanoserde::DeJson::deserialize_json(&json).unwrap());
+            items_fxses.insert(key, emitter_cache);
+        }
+
+        let mut items_sounds = HashMap::new();
+        for (key, path) in sounds {
+            items_sounds.insert(key, load_sound(&path).await?);
+        }
+
+        Ok(Effects {
+            hit_fxses,
+            explosion_fxses,
+            life_ui_explosion_fxses,
+            jump_sound,
+            pickup_sound,
+            player_landing_sound,
+            player_throw_sound,
+            player_die_sound,
+            items_fxses,
+            items_sounds,
+        })
+    }
+
+    /// "tiled_name/id" from "Item::sounds"
+    /// Items do not have to have all the sounds, a missing one is just not played
+    fn play_item_sound(&self, tiled_name: &str, id: &str) {
+        if let Some(sound) = self.items_sounds.get(&format!("{}/{}", tiled_name, id)) {
+            audio::play_sound_once(*sound);
+        }
+    }
+}
+
+#[allow(dead_code)]
+struct Resources {
     tiled_map: tiled::Map,
     collision_world: CollisionWorld,
     whale_green: Texture2D,
     whale_blue: Texture2D,
     whale_boots_blue: Texture2D,
     whale_boots_green: Texture2D,
+    whale_orange: Texture2D,
+    whale_purple: Texture2D,
+    whale_boots_orange: Texture2D,
+    whale_boots_purple: Texture2D,
     broken_turtleshell: Texture2D,
     turtleshell: Texture2D,
+    broken_shield: Texture2D,
+    shield: Texture2D,
     background_01: Texture2D,
     background_02: Texture2D,
     background_03: Texture2D,
     decorations: Texture2D,
-    jump_sound: audio::Sound,
-    shoot_sound: audio::Sound,
-    sword_sound: audio::Sound,
-    pickup_sound: audio::Sound,
-    player_landing_sound: audio::Sound,
-    player_throw_sound: audio::Sound,
-    player_die_sound: audio::Sound,
     items_textures: HashMap<String, Texture2D>,
-    items_fxses: HashMap<String, EmittersCache>,
+    effects: Option<Effects>,
 }
 
 impl Resources {
     // TODO: fix macroquad error type here
     async fn new(map: &str) -> Result<Resources, macroquad::prelude::FileError> {
-        let tileset = load_texture("assets/tileset.png").await?;
-        tileset.set_filter(FilterMode::Nearest);
-
-        let decorations = load_texture("assets/decorations1.png").await?;
-        decorations.set_filter(FilterMode::Nearest);
-
-        let whale_green = load_texture("assets/Whale/Whale(76x66)(Green).png").await?;
-        whale_green.set_filter(FilterMode::Nearest);
-
-        let whale_blue = load_texture("assets/Whale/Whale(76x66)(Blue).png").await?;
-        whale_blue.set_filter(FilterMode::Nearest);
-
-        let whale_boots_green = load_texture("assets/Whale/WhaleBoots(76x66)(Green).png").await?;
-        whale_boots_green.set_filter(FilterMode::Nearest);
+        let texture = |path: &str| {
+            let path = path.to_string();
+            async move {
+                let texture = load_texture(&path).await?;
+                texture.set_filter(FilterMode::Nearest);
+                Ok(texture)
+            }
+        };
 
-        let whale_boots_blue = load_texture("assets/Whale/WhaleBoots(76x66)(Blue).png").await?;
-        whale_boots_blue.set_filter(FilterMode::Nearest);
+        let tileset = texture("assets/tileset.png").await?;
+        let decorations = texture("assets/decorations1.png").await?;
+        let whale_green = texture("assets/Whale/Whale(76x66)(Green).png").await?;
+        let whale_blue = texture("assets/Whale/Whale(76x66)(Blue).png").await?;
+        let whale_boots_green = texture("assets/Whale/WhaleBoots(76x66)(Green).png").await?;
+        let whale_boots_blue = texture("assets/Whale/WhaleBoots(76x66)(Blue).png").await?;
+        let whale_orange = texture("assets/Whale/Whale(76x66)(Orange).png").await?;
+        let whale_purple = texture("assets/Whale/Whale(76x66)(Purple).png").await?;
+        let whale_boots_orange = texture("assets/Whale/WhaleBoots(76x66)(Orange).png").await?;
+        let whale_boots_purple = texture("assets/Whale/WhaleBoots(76x66)(Purple).png").await?;
+        let broken_turtleshell = texture("assets/Whale/BrokenTurtleShell(32x32).png").await?;
+        let turtleshell = texture("assets/Whale/TurtleShell(32x32).png").await?;
+        let broken_shield = texture("assets/Whale/BrokenShield(32x32).png").await?;
+        let shield = texture("assets/Whale/Shield(32x32).png").await?;
+        let background_01 = texture("assets/Background/01.png").await?;
+        let background_02 = texture("assets/Background/02.png").await?;
+        let background_03 = texture("assets/Background/03.png").await?;
+
+        let (tiled_map, collision_world) = Self::load_map(map, tileset, decorations).await?;
+
+        let textures = storage::get::<items::ItemRegistry>().resources(|item| &item.textures);
+        let mut items_textures = HashMap::new();
+        for (key, path) in textures {
+            items_textures.insert(key, texture(&path).await?);
+        }
 
-        let broken_turtleshell = load_texture("assets/Whale/BrokenTurtleShell(32x32).png").await?;
-        broken_turtleshell.set_filter(FilterMode::Nearest);
+        let effects = Some(Effects::new().await?);
 
-        let turtleshell = load_texture("assets/Whale/TurtleShell(32x32).png").await?;
-        turtleshell.set_filter(FilterMode::Nearest);
+        #[allow(clippy::inconsistent_struct_constructor)]
+        Ok(Resources {
+            tiled_map,
+            collision_world,
+            whale_blue,
+            whale_green,
+            whale_boots_blue,
+            whale_boots_green,
+            whale_orange,
+            whale_purple,
+            whale_boots_orange,
+            whale_boots_purple,
+            turtleshell,
+            broken_turtleshell,
+            shield,
+            broken_shield,
+            background_01,
+            background_02,
+            background_03,
+            decorations,
+            items_textures,
+            effects,
+        })
+    }
 
-        let background_01 = load_texture("assets/Background/01.png").await?;
-        background_01.set_filter(FilterMode::Nearest);
+    /// Only the map and the collision world, for running the simulation
+    /// with nothing to show. The map is the only file read: textures are empty
+    /// (items still find theirs by name), sounds and particles are not there
+    async fn headless(map: &str) -> Result<Resources, macroquad::prelude::FileError> {
+        let (tiled_map, collision_world) =
+            Self::load_map(map, Texture2D::empty(), Texture2D::empty()).await?;
 
-        let background_02 = load_texture("assets/Background/02.png").await?;
-        background_02.set_filter(FilterMode::Nearest);
+        let items_textures = storage::get::<items::ItemRegistry>()
+            .resources(|item| &item.textures)
+            .into_iter()
+            .map(|(key, _)| (key, Texture2D::empty()))
+            .collect();
 
-        let background_03 = load_texture("assets/Background/03.png").await?;
-        background_03.set_filter(FilterMode::Nearest);
+        Ok(Resources {
+            tiled_map,
+            collision_world,
+            whale_green: Texture2D::empty(),
+            whale_blue: Texture2D::empty(),
+            whale_boots_blue: Texture2D::empty(),
+            whale_boots_green: Texture2D::empty(),
+            whale_orange: Texture2D::empty(),
+            whale_purple: Texture2D::empty(),
+            whale_boots_orange: Texture2D::empty(),
+            whale_boots_purple: Texture2D::empty(),
+            broken_turtleshell: Texture2D::empty(),
+            turtleshell: Texture2D::empty(),
+            broken_shield: Texture2D::empty(),
+            shield: Texture2D::empty(),
+            background_01: Texture2D::empty(),
+            background_02: Texture2D::empty(),
+            background_03: Texture2D::empty(),
+            decorations: Texture2D::empty(),
+            items_textures,
+            effects: None,
+        })
+    }
 
-        let jump_sound = load_sound("assets/sounds/jump.wav").await?;
-        let shoot_sound = load_sound("assets/sounds/shoot.ogg").await?;
-        let sword_sound = load_sound("assets/sounds/sword.wav").await?;
-        let pickup_sound = load_sound("assets/sounds/pickup.wav").await?;
-        let player_landing_sound = load_sound("assets/sounds/player_landing.wav").await?;
-        let player_throw_sound = load_sound("assets/sounds/throw_noiz.wav").await?;
-        let player_die_sound = load_sound("assets/sounds/fish_fillet.wav").await?;
+    /// The tiled map and the collision world built from its "main layer"
+    async fn load_map(
+        map: &str,
+        tileset: Texture2D,
+        decorations: Texture2D,
+    ) -> Result<(tiled::Map, CollisionWorld), macroquad::prelude::FileError> {
+        let broken_map = |err: String| {
+            let err = std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+            FileError::new(miniquad::fs::Error::IOError(err), map)
+        };
 
-        let tiled_map_json = load_string(map).await.unwrap();
+        let tiled_map_json = load_string(map).await?;
         let tiled_map = tiled::load_map(
             &tiled_map_json,
             &[("tileset.png", tileset), ("decorations1.png", decorations)],
             &[],
         )
-        .unwrap();
+        .map_err(|err| broken_map(format!("{:?}", err)))?;
+
+        // the map is as broken as with a typo in its json, refuse to play it
+        let unknown = {
+            let registry = storage::get::<items::ItemRegistry>();
+            tiled_map.layers["items"]
+                .objects
+                .iter()
+                .find(|object| registry.get(&object.name).is_none())
+                .map(|object| {
+                    format!(
+                        "unknown item \"{}\" at {}x{}",
+                        object.name, object.world_x, object.world_y
+                    )
+                })
+        };
+        if let Some(unknown) = unknown {
+            return Err(broken_map(unknown));
+        }
 
         let mut static_colliders = vec![];
         for (_x, _y, tile) in tiled_map.tiles("main layer", None) {
@@ -130,74 +346,32 @@ impl Resources {
             1,
         );
 
-        const HIT_FX: &str = include_str!("../assets/fxses/hit.json");
-        const EXPLOSION_FX: &str = include_str!("../assets/fxses/explosion.json");
-        const LIFE_UI_FX: &str = include_str!("../assets/fxses/life_ui_explosion.json");
-
-        let hit_fxses = EmittersCache::new(nanoserde::DeJson::deserialize_json(HIT_FX).unwrap());
-        let explosion_fxses =
-            EmittersCache::new(nanoserde::DeJson::deserialize_json(EXPLOSION_FX).unwrap());
-        let life_ui_explosion_fxses =
-            EmittersCache::new(nanoserde::DeJson::deserialize_json(LIFE_UI_FX).unwrap());
-
-        let mut items_textures = HashMap::new();
-        let mut items_fxses = HashMap::new();
-        for item in items::ITEMS {
-            for (id, path) in item.textures {
-                let texture = load_texture(path).await?;
-                texture.set_filter(FilterMode::Nearest);
-                items_textures.insert(format!("{}/{}", item.tiled_name, id.to_string()), texture);
-            }
-
-            for (id, path) in item.fxses {
-                let json = load_string(path).await?;
-                let emitter_cache =
-                    EmittersCache::new(nanoserde::DeJson::deserialize_json(&json).unwrap());
-                items_fxses.insert(
-                    format!("{}/{}", item.tiled_name, id.to_string()),
-                    emitter_cache,
-                );
-            }
-        }
-
-        #[allow(clippy::inconsistent_struct_constructor)]
-        Ok(Resources {
-            hit_fxses,
-            explosion_fxses,
-            life_ui_explosion_fxses,
-            items_fxses,
-            tiled_map,
-            collision_world,
-            whale_blue,
-            whale_green,
-            whale_boots_blue,
-            whale_boots_green,
-            turtleshell,
-            broken_turtleshell,
-            background_01,
-            background_02,
-            background_03,
-            decorations,
-            jump_sound,
-            shoot_sound,
-            sword_sound,
-            pickup_sound,
-            player_landing_sound,
-            player_throw_sound,
-            player_die_sound,
-            items_textures,
-        })
+        Ok((tiled_map, collision_world))
     }
 }
 
-async fn game(map: &str, game_type: GameType) {
-    use nodes::{Camera, Decoration, Fxses, LevelBackground, LocalNetwork, Network, Player};
+/// One round of the match
+/// "round_wins" are the scores after the previous rounds, returns the updated ones,
+/// None if the match was abandoned
+/// A map that can't be loaded is reported on the screen and returned as an error,
+/// the round is not played
+async fn game(
+    map: &str,
+    game_type: &GameType,
+    rules: nodes::MatchRules,
+    round: usize,
+    round_wins: &[u32],
+) -> Result<Option<Vec<u32>>, FileError> {
+    use nodes::{
+        Camera, Decoration, Fxses, GameState, LevelBackground, LocalNetwork, Network, Player,
+        ReplayPlayback,
+    };
 
     let resources_loading = start_coroutine({
         let map = map.to_string();
         async move {
-            let resources = Resources::new(&map).await.unwrap();
-            storage::store(resources);
+            let resources = Resources::new(&map).await;
+            storage::store(Some(resources));
         }
     });
 
@@ -217,6 +391,31 @@ async fn game(map: &str, game_type: GameType) {
         next_frame().await;
     }
 
+    let resources = storage::get_mut::<Option<Result<Resources, FileError>>>()
+        .take()
+        .unwrap();
+    match resources {
+        Ok(resources) => storage::store(resources),
+        Err(err) => {
+            let error_time = get_time() + 3.;
+            while get_time() < error_time {
+                clear_background(BLACK);
+                draw_text(&err.to_string(), 20., screen_height() / 2.0, 20., RED);
+                draw_text(
+                    "Skipping the level",
+                    20.,
+                    screen_height() / 2.0 + 30.,
+                    20.,
+                    WHITE,
+                );
+
+                next_frame().await;
+            }
+
+            return Err(err);
+        }
+    }
+
     let battle_music = if map == "assets/map.json" {
         load_sound("assets/music/across the pond.ogg")
             .await
@@ -258,49 +457,187 @@ async fn game(map: &str, game_type: GameType) {
 
     drop(resources);
 
-    let player1 = scene::add_node(Player::new(0, 0));
-    let player2 = scene::add_node(Player::new(1, 1));
+    let seed = match game_type {
+        GameType::Local(..) => macroquad::miniquad::date::now() as u64,
+        GameType::Network { seed, .. } => seed.wrapping_add(round as u64),
+        GameType::Replay(replay) => replay.header.seed,
+    };
+    simulation::srand(seed);
+
+    let players_count = game_type.players_count();
+    assert!(
+        (2..=MAX_PLAYERS).contains(&players_count),
+        "Only 2-{} player games are supported",
+        MAX_PLAYERS
+    );
+    let players = (0..players_count)
+        .map(|id| scene::add_node(Player::new(id as u8, id as i32)))
+        .collect::<Vec<_>>();
 
     let local_game = matches!(game_type, GameType::Local(..));
+    let items_layout = match game_type {
+        GameType::Replay(replay) => replay.header.items.clone(),
+        _ => {
+            let registry = storage::get::<items::ItemRegistry>();
+            let mut layout = vec![];
+            for object in &objects {
+                // Resources::load already refused the maps with unknown items
+                let item_desc = registry.get(&object.name).unwrap();
+
+                if local_game || item_desc.network_ready {
+                    layout.push(replay::ReplayItem {
+                        name: item_desc.tiled_name.clone(),
+                        x: object.world_x + item_desc.tiled_offset.0,
+                        y: object.world_y + item_desc.tiled_offset.1,
+                    });
+                }
+            }
+            layout
+        }
+    };
+
+    if let GameType::Replay(..) = game_type {
+        replay::stop_recording();
+    } else {
+        replay::start_recording(&replay::ReplayHeader {
+            map: map.to_string(),
+            seed,
+            players: players_count as u32,
+            rules,
+            round_wins: round_wins.to_vec(),
+            items: items_layout.clone(),
+        });
+    }
+
+    let game_state = scene::add_node(GameState::new(rules, players.clone(), round_wins.to_vec()));
+
     match game_type {
         GameType::Local(players_input) => {
-            assert!(
-                players_input.len() == 2,
-                "Only 2 player games are supported now"
-            );
-            scene::add_node(LocalNetwork::new(players_input, player1, player2));
+            scene::add_node(LocalNetwork::new(players_input.clone(), players));
         }
         GameType::Network {
-            input_scheme,
-            socket,
+            input_schemes,
+            peers_players,
+            connection,
             id,
+            mode,
+            ..
         } => {
-            scene::add_node(Network::new(id, socket, input_scheme, player1, player2));
+            scene::add_node(Network::new(
+                *id,
+                connection.clone(),
+                round as u8,
+                *mode,
+                input_schemes.clone(),
+                peers_players.clone(),
+                players,
+            ));
+        }
+        GameType::Replay(replay) => {
+            scene::add_node(ReplayPlayback::new(replay.clone(), players));
         }
     }
 
     scene::add_node(Camera::new(bounds));
 
-    for object in &objects {
-        for item_desc in items::ITEMS {
-            if object.name == item_desc.tiled_name && (local_game || item_desc.network_ready) {
-                (item_desc.constructor)(vec2(
-                    object.world_x + item_desc.tiled_offset.0,
-                    object.world_y + item_desc.tiled_offset.1,
-                ));
-            }
-        }
+    for item in &items_layout {
+        items::spawn(&item.name, vec2(item.x, item.y));
     }
 
     scene::add_node(Fxses {});
 
-    loop {
+    let result = loop {
         {
             let mut gui_resources = storage::get_mut::<crate::gui::GuiResources>();
             gui_resources.gamepads.update();
         }
 
         next_frame().await;
+
+        let game_state = scene::get_node(game_state);
+        if game_state.want_quit {
+            if let Some(mut network) = scene::find_node_by_type::<Network>() {
+                network.leave();
+            }
+            break None;
+        }
+        if let Some(network) = scene::find_node_by_type::<Network>() {
+            if network.peer_left() {
+                break None;
+            }
+        }
+        if game_state.round_done() {
+            break Some(game_state.round_wins().to_vec());
+        }
+    };
+
+    // remote peers may still need our last inputs to get to the end of the round,
+    // or a few more of the Leave messages to get that the match is over
+    let leave_time = get_time() + 1.;
+    while get_time() < leave_time {
+        let done = match scene::find_node_by_type::<Network>() {
+            Some(network) => result.is_some() && network.settled(),
+            None => true,
+        };
+        if done {
+            break;
+        }
+
+        next_frame().await;
+    }
+
+    audio::stop_sound(battle_music);
+
+    Ok(result)
+}
+
+/// Rounds on the rotating levels, until somebody wins enough of them
+async fn game_match(
+    game_type: GameType,
+    rules: nodes::MatchRules,
+    level_order: LevelOrder,
+    first_map: &str,
+) {
+    let seed = match game_type {
+        GameType::Local(..) => macroquad::miniquad::date::now() as u64,
+        GameType::Network { seed, .. } => seed,
+        GameType::Replay(..) => unreachable!(),
+    };
+    let levels = {
+        let gui_resources = storage::get::<gui::GuiResources>();
+        level_rotation(&gui_resources.level_maps(), first_map, level_order, seed)
+    };
+
+    let mut round_wins = vec![0; game_type.players_count()];
+    let mut played = 0;
+    // levels failed to load in a row, all of them - nothing to play
+    let mut skipped = 0;
+    for round in 0.. {
+        let map = &levels[round % levels.len()];
+
+        let result = game(map, &game_type, rules, round, &round_wins).await;
+        scene::clear();
+
+        // a broken level is skipped the same way on all the network peers,
+        // they all have the same levels
+        round_wins = match result {
+            Ok(Some(round_wins)) => round_wins,
+            Ok(None) => return,
+            Err(_) if skipped + 1 < levels.len() => {
+                skipped += 1;
+                continue;
+            }
+            Err(_) => return,
+        };
+        skipped = 0;
+        played += 1;
+        if round_wins.iter().any(|wins| *wins >= rules.rounds_to_win) {
+            return;
+        }
+
+        let next_map = &levels[(round + 1) % levels.len()];
+        let skippable = !matches!(game_type, GameType::Network { .. });
+        gui::intermission::scoreboard(&round_wins, rules, played, next_map, skippable).await;
     }
 }
 
@@ -316,23 +653,52 @@ fn window_conf() -> Conf {
 
 #[macroquad::main(window_conf)]
 async fn main() {
+    items::load_items().await;
+
+    if std::env::args().any(|arg| arg == "--check-items") {
+        let success = determinism::check_items().await;
+        std::process::exit(if success { 0 } else { 1 });
+    }
+
     {
         let gui_resources = gui::GuiResources::load().await;
         storage::store(gui_resources);
     }
+    storage::store(input::Bindings::load().await);
+
+    // to reproduce a bug report: --replay replays/1234.replay
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(ix) = args.iter().position(|arg| arg == "--replay") {
+        let path = args.get(ix + 1).expect("--replay needs a file path");
+        let replay = match replay::Replay::load(path) {
+            Ok(replay) => replay,
+            Err(err) => {
+                println!("Can't load the replay: {}", err);
+                std::process::exit(1);
+            }
+        };
+
+        let map = replay.header.map.clone();
+        let rules = replay.header.rules;
+        let round_wins = replay.header.round_wins.clone();
+        // a broken map is already reported by "game"
+        let _ = game(&map, &GameType::Replay(replay), rules, 0, &round_wins).await;
+
+        scene::clear();
+    }
 
     rand::srand(0);
 
     loop {
-        let game_type = gui::main_menu::game_type().await;
+        let (game_type, rules, level_order) = gui::main_menu::game_type().await;
 
-        let map = match game_type {
+        let first_map = match game_type {
             GameType::Local(..) => gui::main_menu::location_select().await,
-            GameType::Network { .. } => "assets/levels/lev01.json".to_string(),
+            // no level select over the network yet, the rotation starts from the first level
+            GameType::Network { .. } => storage::get::<gui::GuiResources>().level_maps()[0].clone(),
+            GameType::Replay(..) => unreachable!(),
         };
 
-        game(&map, game_type).await;
-
-        scene::clear();
+        game_match(game_type, rules, level_order, &first_map).await;
     }
 }
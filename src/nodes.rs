@@ -1,15 +1,19 @@
 mod camera;
 mod decoration;
 mod fxses;
+mod game_state;
 mod level_background;
 mod local_network;
 pub mod network;
 mod player;
+mod replay_playback;
 
 pub use camera::Camera;
 pub use decoration::Decoration;
 pub use fxses::Fxses;
+pub use game_state::{GameState, MatchRules};
 pub use level_background::LevelBackground;
 pub use local_network::LocalNetwork;
 pub use network::Network;
 pub use player::Player;
+pub use replay_playback::ReplayPlayback;
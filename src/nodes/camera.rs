@@ -28,6 +28,8 @@ pub struct Camera {
     shake: Vec<Shake>,
     noisegen: NoiseGenerator,
     noisegen_position: f32,
+    /// The one last set to the scene, for mouse aiming
+    macroquad_camera: Camera2D,
 
     pub manual: Option<(Vec2, f32)>,
 }
@@ -43,8 +45,13 @@ impl Camera {
             manual: None,
             noisegen: NoiseGenerator::new(5),
             noisegen_position: 5.0,
+            macroquad_camera: Camera2D::default(),
         }
     }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        self.macroquad_camera.screen_to_world(point)
+    }
 }
 
 #[allow(dead_code)]
@@ -164,7 +171,10 @@ impl Camera {
 impl scene::Node for Camera {
     fn fixed_update(mut node: RefMut<Self>) {
         {
-            let players = scene::find_nodes_by_type::<Player>();
+            // fishes out of lives are not worth watching, unless nobody is left
+            let anybody_alive = scene::find_nodes_by_type::<Player>().any(|p| !p.eliminated);
+            let players = scene::find_nodes_by_type::<Player>()
+                .filter(|player| !anybody_alive || !player.eliminated);
             let aspect = screen_width() / screen_height();
 
             let mut players_amount = 0;
@@ -233,6 +243,7 @@ impl scene::Node for Camera {
             ..Camera2D::default()
         };
 
+        node.macroquad_camera = macroquad_camera;
         scene::set_camera(0, Some(macroquad_camera));
     }
 }
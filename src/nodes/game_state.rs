@@ -1,41 +1,117 @@
 use macroquad::{
-    experimental::{
-        collections::storage,
-        coroutines::{start_coroutine, wait_seconds},
-        scene::{self, Handle, RefMut},
-    },
+    color,
+    experimental::scene::{self, Handle, HandleUntyped, RefMut},
     prelude::*,
     window::miniquad::*,
 };
 
-use crate::{gui::pause_menu, nodes::Player};
+use nanoserde::{DeBin, SerBin};
 
-#[derive(Clone, Copy, PartialEq)]
+use crate::{
+    capabilities::{NetworkReplicate, Snapshot},
+    gui::pause_menu,
+    nodes::{Camera, Player},
+    simulation,
+    snapshot::{SnapshotReader, SnapshotWriter},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, DeBin, SerBin)]
+pub enum WinCondition {
+    /// Last fish with lives left takes the round
+    Lives(u32),
+    /// First fish to get that many kills takes the round
+    Kills(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, DeBin, SerBin)]
+pub struct MatchRules {
+    pub win_condition: WinCondition,
+    /// Round wins needed to win the match
+    pub rounds_to_win: u32,
+}
+
+impl MatchRules {
+    /// What the main menu offers
+    pub const PRESETS: &'static [(&'static str, MatchRules)] = &[
+        (
+            "3 lives, 2 rounds to win",
+            MatchRules {
+                win_condition: WinCondition::Lives(3),
+                rounds_to_win: 2,
+            },
+        ),
+        (
+            "5 kills, 2 rounds to win",
+            MatchRules {
+                win_condition: WinCondition::Kills(5),
+                rounds_to_win: 2,
+            },
+        ),
+        (
+            "1 life, 3 rounds to win",
+            MatchRules {
+                win_condition: WinCondition::Lives(1),
+                rounds_to_win: 3,
+            },
+        ),
+        (
+            "10 kills, single round",
+            MatchRules {
+                win_condition: WinCondition::Kills(10),
+                rounds_to_win: 1,
+            },
+        ),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, DeBin, SerBin)]
 enum State {
     Starting,
-    Paused,
     InProgress,
+    RoundOver,
     Finished,
 }
 
 /// Mediator of a game
-/// Do pauses, manage little custscenes like game start/game finish etc
+/// Keeps the score, ends the round, do little custscenes like
+/// round start/match finish etc
 /// idk the worst name for a node, but it really is about a current state of a game
+///
+/// Everything deciding who wins is a part of the simulation: it runs in
+/// "network_update", counts simulation frames instead of seconds and goes
+/// into the snapshots.
 pub struct GameState {
+    /// Players are frozen for the round start countdown
     pub game_paused: bool,
+    /// Left the match through the exit dialog
     pub want_quit: bool,
 
-    material: Material,
+    rules: MatchRules,
+    players: Vec<Handle<Player>>,
+
     state: State,
-    start_label: String,
-    win_label: String,
+    /// Simulation frames spent in the current state
+    state_frames: u32,
+    lives: Vec<u32>,
+    kills: Vec<u32>,
+    /// Including the previous rounds of the match
+    round_wins: Vec<u32>,
+    /// None for a draw
+    round_winner: Option<u8>,
 
-    winner: Option<Handle<Player>>,
-    k: f32,
+    material: Material,
+    quit_dialog: bool,
 }
 
 impl GameState {
-    pub fn new() -> GameState {
+    const START_LABELS: [&'static str; 4] = ["FISH", "FIGHT", "KILL", "FISH"];
+    const START_LABEL_TIME: f32 = 0.11;
+    const ROUND_OVER_TIME: f32 = 3.0;
+    /// Winner close-up, winner label, match is over
+    const FINISH_TIMINGS: [f32; 3] = [0.7, 2.2, 4.2];
+
+    /// "round_wins" are the scores after the previous rounds of the match
+    pub fn new(rules: MatchRules, players: Vec<Handle<Player>>, round_wins: Vec<u32>) -> GameState {
         let material = load_material(
             VERTEX,
             FRAGMENT,
@@ -54,198 +130,324 @@ impl GameState {
         )
         .unwrap();
 
+        assert_eq!(players.len(), round_wins.len());
+
+        let lives = match rules.win_condition {
+            WinCondition::Lives(lives) => lives,
+            WinCondition::Kills(_) => 0,
+        };
+
         GameState {
-            material,
             game_paused: true,
             want_quit: false,
+            rules,
+            players,
             state: State::Starting,
-            start_label: "".to_string(),
-            win_label: "".to_string(),
-            k: 0.0,
-            winner: None,
+            state_frames: 0,
+            lives: vec![lives; round_wins.len()],
+            kills: vec![0; round_wins.len()],
+            round_wins,
+            round_winner: None,
+            material,
+            quit_dialog: false,
         }
     }
-}
 
-impl scene::Node for GameState {
-    fn ready(mut node: RefMut<Self>) {
-        let handle = node.handle();
+    /// Called by the fish being killed, "killer" is None for the suicides
+    /// and the accidents
+    pub fn count_death(&mut self, player: u8, killer: Option<u8>) {
+        if self.state != State::InProgress {
+            return;
+        }
 
-        node.state = State::Starting;
+        let lives = &mut self.lives[player as usize];
+        *lives = lives.saturating_sub(1);
 
-        start_coroutine(async move {
-            {
-                let mut node = scene::get_node(handle);
-                node.start_label = "FISH".to_string();
-            }
-            wait_seconds(0.11).await;
-            {
-                let mut node = scene::get_node(handle);
-                node.start_label = "FIGHT".to_string();
+        if let Some(killer) = killer {
+            if killer != player {
+                self.kills[killer as usize] += 1;
             }
-            wait_seconds(0.11).await;
-            {
-                let mut node = scene::get_node(handle);
-                node.start_label = "KILL".to_string();
-            }
-            wait_seconds(0.11).await;
-            {
-                let mut node = scene::get_node(handle);
-                node.start_label = "FISH".to_string();
-            }
-            wait_seconds(0.11).await;
+        }
+    }
 
-            let mut node = scene::get_node(handle);
-            node.state = State::InProgress;
-            node.game_paused = false;
-        });
+    /// Should the dead fish stay dead till the end of the round
+    pub fn out_of_lives(&self, player: u8) -> bool {
+        matches!(self.rules.win_condition, WinCondition::Lives(_))
+            && self.lives[player as usize] == 0
     }
 
-    fn draw(mut node: RefMut<Self>) {
-        if node.state == State::Starting {
-            push_camera_state();
-            set_default_camera();
-
-            draw_rectangle(
-                0.0,
-                0.0,
-                screen_width(),
-                screen_height(),
-                Color::new(0., 0., 0., 0.8),
-            );
+    fn set_state(&mut self, state: State) {
+        self.state = state;
+        self.state_frames = 0;
+        self.game_paused = state == State::Starting;
+    }
 
-            let text_size = measure_text(&node.start_label, None, 16, 1.);
+    /// Some(None) - the round is over, but nobody won it
+    fn round_result(&self) -> Option<Option<u8>> {
+        match self.rules.win_condition {
+            WinCondition::Lives(_) => {
+                let mut alive =
+                    (0..self.players.len() as u8).filter(|id| self.lives[*id as usize] > 0);
+                let first = alive.next();
+
+                if alive.next().is_none() {
+                    Some(first)
+                } else {
+                    None
+                }
+            }
+            WinCondition::Kills(limit) => (0..self.players.len() as u8)
+                .find(|id| self.kills[*id as usize] >= limit)
+                .map(Some),
+        }
+    }
 
-            let pos = vec2(
-                screen_width() / 2. - text_size.width / 2. * 20.,
-                screen_height() / 2. + text_size.height / 2. * 20.,
-            );
+    pub fn round_wins(&self) -> &[u32] {
+        &self.round_wins
+    }
 
-            gl_use_material(node.material);
-            draw_text_ex(
-                &node.start_label,
-                pos.x,
-                pos.y,
-                TextParams {
-                    font_size: 16,
-                    font_scale: 20.,
-                    ..Default::default()
-                },
-            );
-            gl_use_default_material();
+    pub fn match_winner(&self) -> Option<u8> {
+        (0..self.players.len() as u8)
+            .find(|id| self.round_wins[*id as usize] >= self.rules.rounds_to_win)
+    }
 
-            pop_camera_state();
+    fn end_round(&mut self, winner: Option<u8>) {
+        if let Some(winner) = winner {
+            self.round_wins[winner as usize] += 1;
         }
+        self.round_winner = winner;
+        self.set_state(State::RoundOver);
+
+        // everybody still standing just waits for the next round
+        for player in &self.players {
+            let mut player = scene::get_node(*player);
+            if player.state() != Player::ST_DEATH {
+                player.set_state(Player::ST_AFTERMATCH);
+            }
+        }
+    }
 
-        if node.state == State::Finished {
-            push_camera_state();
-            set_default_camera();
+    fn network_update(mut node: RefMut<Self>) {
+        node.state_frames += 1;
 
-            draw_rectangle(
-                0.0,
-                0.0,
-                screen_width(),
-                screen_height(),
-                Color::new(0., 0., 0., node.k.min(0.5)),
-            );
-            if node.k >= 0.0001 {
-                node.k += get_frame_time();
+        match node.state {
+            State::Starting => {
+                let duration =
+                    simulation::frames(Self::START_LABEL_TIME) * Self::START_LABELS.len() as u32;
+                if node.state_frames >= duration {
+                    node.set_state(State::InProgress);
+                }
+            }
+            State::InProgress => {
+                if let Some(winner) = node.round_result() {
+                    node.end_round(winner);
+                }
             }
+            State::RoundOver => {
+                // without a match winner it just stays here, until the next round
+                if node.state_frames >= simulation::frames(Self::ROUND_OVER_TIME)
+                    && node.match_winner().is_some()
+                {
+                    node.set_state(State::Finished);
+                }
+            }
+            State::Finished => {}
+        }
+    }
 
-            let text_size = measure_text(&node.win_label, None, 16, 1.);
+    /// Time to move on to the next round, or to the main menu if the match is won
+    /// Still a part of the simulation, so all the peers are done on the same frame
+    pub fn round_done(&self) -> bool {
+        match self.state {
+            State::RoundOver => self.seconds() >= Self::ROUND_OVER_TIME,
+            State::Finished => self.seconds() >= Self::FINISH_TIMINGS[2],
+            _ => false,
+        }
+    }
 
-            let pos = vec2(
-                screen_width() / 2. - text_size.width / 2. * 8.,
-                screen_height() / 2. + text_size.height / 2. * 8.,
-            );
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<GameState>();
+            GameState::network_update(node);
+        }
 
-            draw_text_ex(
-                &node.win_label,
-                pos.x,
-                pos.y,
-                TextParams {
-                    font_size: 16,
-                    font_scale: 8.,
-                    ..Default::default()
-                },
-            );
-            gl_use_default_material();
-            pop_camera_state();
+        NetworkReplicate { network_update }
+    }
 
-            if let Some(winner) = node.winner {
-                let player = scene::get_node(winner);
-                Player::draw(player);
-            }
+    fn snapshot_capabilities() -> Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<GameState>();
+
+            writer.write(&node.state);
+            writer.write(&node.state_frames);
+            writer.write(&node.lives);
+            writer.write(&node.kills);
+            writer.write(&node.round_wins);
+            writer.write(&node.round_winner);
         }
-        if node.state == State::Paused {
-            match pause_menu::gui() {
-                pause_menu::PauseResult::Quit => {
-                    node.want_quit = true;
-                }
-                pause_menu::PauseResult::Close => {
-                    node.state = State::InProgress;
-                    node.game_paused = true;
-                }
-                pause_menu::PauseResult::Nothing => {}
-            }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<GameState>();
+
+            node.state = reader.read();
+            node.state_frames = reader.read();
+            node.lives = reader.read();
+            node.kills = reader.read();
+            node.round_wins = reader.read();
+            node.round_winner = reader.read();
+            node.game_paused = node.state == State::Starting;
+        }
+
+        Snapshot {
+            save,
+            load,
+            rebuild: None,
         }
     }
 
-    fn update(mut node: RefMut<Self>) {
-        let input_axis = storage::get::<crate::input_axis::InputAxises>();
-        if input_axis.start_pressed {
-            if node.state == State::InProgress {
-                node.state = State::Paused;
-                node.game_paused = true;
-            } else if node.state == State::Paused {
-                node.state = State::InProgress;
-                node.game_paused = false;
-            }
+    fn seconds(&self) -> f32 {
+        self.state_frames as f32 * simulation::FRAME_TIME
+    }
+
+    pub fn player_color(id: u8) -> Color {
+        // same as the whale textures
+        match id % 4 {
+            0 => color::BLUE,
+            1 => color::GREEN,
+            2 => color::ORANGE,
+            _ => color::PURPLE,
         }
+    }
+
+    fn draw_big_label(label: &str, scale: f32) {
+        let text_size = measure_text(label, None, 16, 1.);
+
+        draw_text_ex(
+            label,
+            screen_width() / 2. - text_size.width / 2. * scale,
+            screen_height() / 2. + text_size.height / 2. * scale,
+            TextParams {
+                font_size: 16,
+                font_scale: scale,
+                ..Default::default()
+            },
+        );
+    }
 
-        if node.state == State::InProgress {
-            // let score = scene::get_node(node.score_counter);
-            // if score.player0_lifes <= 0 || score.player1_lifes <= 0 {
-            //     node.state = State::Finished;
-            //     node.game_paused = true;
-            //     let handle = node.handle();
-            //     start_coroutine(Self::win_coroutine(
-            //         handle,
-            //         if score.player0_lifes <= 0 { 1 } else { 0 },
-            //     ));
-            // }
+    fn draw_score(&self) {
+        for (ix, (lives, kills)) in self.lives.iter().zip(&self.kills).enumerate() {
+            let score = match self.rules.win_condition {
+                WinCondition::Lives(_) => format!("lives {}", lives),
+                WinCondition::Kills(limit) => format!("kills {}/{}", kills, limit),
+            };
+
+            draw_text(
+                &format!(
+                    "P{} {} rounds {}/{}",
+                    ix + 1,
+                    score,
+                    self.round_wins[ix],
+                    self.rules.rounds_to_win
+                ),
+                20.,
+                30. + ix as f32 * 25.,
+                25.,
+                Self::player_color(ix as u8),
+            );
         }
     }
 }
 
-impl GameState {
-    async fn win_coroutine(handle: Handle<GameState>, winner: i32) {
-        wait_seconds(0.7).await;
-
-        {
-            let player = scene::find_nodes_by_type::<crate::nodes::Player>()
-                .find(|node| node.controller_id == winner)
-                .unwrap();
-            let mut camera = scene::find_node_by_type::<crate::nodes::Camera>().unwrap();
-            camera.manual = Some((player.body.pos + vec2(-30., 30.), 90.));
-
-            let mut node = scene::get_node(handle);
-            node.winner = Some(player.handle());
-            node.k = 0.0001;
-        }
+impl scene::Node for GameState {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
+    }
 
-        wait_seconds(1.5).await;
+    fn update(node: RefMut<Self>) {
+        // close-up on the match winner
+        let mut camera = scene::find_node_by_type::<Camera>().unwrap();
+        camera.manual = None;
 
-        {
-            let mut node = scene::get_node(handle);
-            node.win_label = "THE FISH ->      ".to_string();
+        if node.state == State::Finished && node.seconds() >= Self::FINISH_TIMINGS[0] {
+            if let Some(winner) = node.match_winner() {
+                let player = scene::get_node(node.players[winner as usize]);
+                camera.manual = Some((player.body.pos + vec2(-30., 30.), 90.));
+            }
+        }
+    }
+
+    fn draw(mut node: RefMut<Self>) {
+        push_camera_state();
+        set_default_camera();
+
+        node.draw_score();
+
+        match node.state {
+            State::Starting => {
+                let label_frames = simulation::frames(Self::START_LABEL_TIME);
+                let ix = (node.state_frames / label_frames) as usize;
+
+                gl_use_material(node.material);
+                Self::draw_big_label(
+                    Self::START_LABELS[ix.min(Self::START_LABELS.len() - 1)],
+                    20.,
+                );
+                gl_use_default_material();
+            }
+            State::RoundOver => {
+                let label = match node.round_winner {
+                    Some(winner) => format!("P{} TAKES THE ROUND", winner + 1),
+                    None => "DRAW".to_string(),
+                };
+                Self::draw_big_label(&label, 4.);
+            }
+            State::Finished => {
+                draw_rectangle(
+                    0.0,
+                    0.0,
+                    screen_width(),
+                    screen_height(),
+                    Color::new(0., 0., 0., node.seconds().min(0.5)),
+                );
+
+                if node.seconds() >= Self::FINISH_TIMINGS[1] {
+                    Self::draw_big_label("THE FISH ->      ", 8.);
+                }
+            }
+            State::InProgress => {}
         }
 
-        wait_seconds(2.0).await;
+        pop_camera_state();
 
-        {
-            let mut node = scene::get_node(handle);
-            node.want_quit = true;
+        if node.state == State::Finished && node.seconds() >= Self::FINISH_TIMINGS[0] {
+            if let Some(winner) = node.match_winner() {
+                let player = scene::get_node(node.players[winner as usize]);
+                <Player as scene::Node>::draw(player);
+            }
+        }
+
+        // the match can't be paused, it may be a network one,
+        // so it is just a "do you really want to leave" dialog
+        if node.quit_dialog {
+            match pause_menu::gui() {
+                pause_menu::PauseResult::Quit => {
+                    node.want_quit = true;
+                    node.quit_dialog = false;
+                }
+                pause_menu::PauseResult::Close => {
+                    node.quit_dialog = false;
+                }
+                pause_menu::PauseResult::Nothing => {}
+            }
+        } else if is_key_pressed(KeyCode::Escape) {
+            node.quit_dialog = true;
         }
     }
 }
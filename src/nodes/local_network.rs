@@ -1,35 +1,40 @@
 //! Node that emulates network for local play
-//! This is temporary solution, in the future "LocalNetwork" node should
-//! be able to mix local/remote players
+//! Local players mixed with remote ones are handled by "Network" itself,
+//! each peer may have a few input schemes
 //!
 //! But, right now, with fixed-delay networking
 //! it is nice to run completely local, no-delay game
 
-use macroquad::experimental::scene::{self, Handle, Node, NodeWith, RefMut};
+use macroquad::experimental::scene::{self, Handle, Node, RefMut};
 
-use crate::{capabilities::NetworkReplicate, input, nodes::Player};
+use crate::{
+    gui::pause_menu,
+    input::{self, Input},
+    nodes::Player,
+    replay, simulation,
+};
 
 pub struct LocalNetwork {
-    player1_input: input::InputScheme,
-    player1: Handle<Player>,
-    player2_input: input::InputScheme,
-    player2: Handle<Player>,
+    players: Vec<(input::InputScheme, Handle<Player>)>,
+    /// Previous frame inputs, for the pressed/released edges
+    inputs: Vec<Input>,
+    /// Players with unplugged gamepads, the game is paused until they are back
+    disconnected: Vec<usize>,
 
     paused: bool,
 }
 
 impl LocalNetwork {
+    /// One input scheme per player, in the same order
     pub fn new(
         players_input: Vec<input::InputScheme>,
-        player1: Handle<Player>,
-        player2: Handle<Player>,
+        players: Vec<Handle<Player>>,
     ) -> LocalNetwork {
-        assert!(players_input.len() == 2);
+        assert_eq!(players_input.len(), players.len());
         LocalNetwork {
-            player1,
-            player2,
-            player1_input: players_input[0],
-            player2_input: players_input[1],
+            inputs: vec![Input::default(); players.len()],
+            players: players_input.into_iter().zip(players).collect(),
+            disconnected: vec![],
             paused: false,
         }
     }
@@ -37,8 +42,28 @@ impl LocalNetwork {
 
 impl Node for LocalNetwork {
     fn fixed_update(mut node: RefMut<Self>) {
-        scene::get_node(node.player1).apply_input(input::collect_input(node.player1_input));
-        scene::get_node(node.player2).apply_input(input::collect_input(node.player2_input));
+        let mut schemes = node
+            .players
+            .iter()
+            .map(|(input_scheme, _)| *input_scheme)
+            .collect::<Vec<_>>();
+        node.disconnected = input::reclaim_gamepads(&mut schemes);
+        for ((input_scheme, _), scheme) in node.players.iter_mut().zip(schemes) {
+            *input_scheme = scheme;
+        }
+
+        let inputs = node
+            .players
+            .iter()
+            .zip(&node.inputs)
+            .map(|((input_scheme, player), previous)| {
+                let origin = scene::get_node(*player).center();
+                input::collect_input(*input_scheme, previous, origin)
+            })
+            .collect::<Vec<_>>();
+        for ((_, player), input) in node.players.iter().zip(&inputs) {
+            scene::get_node(*player).apply_input(*input);
+        }
 
         if macroquad::input::is_key_down(macroquad::prelude::KeyCode::Z) {
             node.paused = true;
@@ -47,10 +72,21 @@ impl Node for LocalNetwork {
             node.paused = false;
         }
 
-        if !node.paused {
-            for NodeWith { node, capability } in scene::find_nodes_with::<NetworkReplicate>() {
-                (capability.network_update)(node);
-            }
+        if !node.paused && node.disconnected.is_empty() {
+            replay::record_frame(&inputs);
+            node.inputs = inputs;
+            simulation::advance();
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if !node.disconnected.is_empty() {
+            let players = node
+                .disconnected
+                .iter()
+                .map(|ix| ix + 1)
+                .collect::<Vec<_>>();
+            pause_menu::disconnected_gui(&players);
         }
     }
 }
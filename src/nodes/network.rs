@@ -1,15 +1,33 @@
 //! Very, very WIP
 //! "Delayed lockstep" networking implementation - first step towards GGPO
-
-use macroquad::experimental::scene::{self, Handle, Node, NodeWith, RefMut};
+//! and a "Rollback" mode on top of it: remote inputs are predicted and,
+//! when prediction turns out to be wrong, the world is restored from a snapshot
+//! and re-simulated with the right inputs
+//!
+//! Each peer owns a few player slots - one for each fish played on that machine,
+//! slots are given out in peer id order. Every message goes to all the other peers,
+//! so inputs and acks carry the peer ids they belong to.
+//!
+//! A match is a few rounds, each with its own Network node starting from frame 0,
+//! while the Connection lives through the whole match. Messages are tagged with the
+//! round number, so late packets from the previous round are not mistaken for the
+//! current round ones.
+
+use macroquad::{
+    color,
+    experimental::scene::{self, Handle, Node, RefMut},
+    prelude::*,
+};
 
 use crate::{
-    capabilities::NetworkReplicate,
+    gui::pause_menu,
     input::{self, Input, InputScheme},
     nodes::Player,
+    replay, simulation,
+    snapshot::WorldSnapshot,
 };
 
-use std::sync::mpsc;
+use std::{ops::Range, rc::Rc, sync::mpsc};
 
 use nanoserde::{DeBin, SerBin};
 
@@ -35,6 +53,42 @@ impl Socket for std::net::UdpSocket {
     }
 }
 
+/// UDP socket talking to a few peers at once
+/// "send" goes to every peer and fails if any of them failed,
+/// "recv" takes a message from any of them
+pub struct LanSocket {
+    pub socket: std::net::UdpSocket,
+    pub peers: Vec<std::net::SocketAddr>,
+}
+
+impl Socket for LanSocket {
+    fn send(&self, buf: &[u8]) -> Option<usize> {
+        // one unreachable peer should not keep the message from the others
+        let sent = self
+            .peers
+            .iter()
+            .map(|peer| self.socket.send_to(buf, peer).ok())
+            .collect::<Vec<_>>();
+
+        sent.into_iter()
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .min()
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> Option<usize> {
+        self.socket.recv_from(buf).ok().map(|(count, _)| count)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn Socket>> {
+        let socket = LanSocket {
+            socket: self.socket.try_clone().ok()?,
+            peers: self.peers.clone(),
+        };
+        Some(Box::new(socket) as Box<dyn Socket>)
+    }
+}
+
 #[cfg(feature = "steamworks")]
 pub mod steam {
     use fishsteam::{Steam, SteamId};
@@ -67,66 +121,70 @@ pub mod steam {
 pub enum Message {
     /// Empty message, used for connection test
     Idle,
+    /// Connection test, carrying the sender's part of the match RNG seed
+    /// Match seed is all the parts xor-ed together, so all peers get the same one
+    Handshake {
+        seed: u64,
+        /// Amount of fishes played on the sender machine
+        players: u8,
+        /// NetworkMode, index in MatchRules::PRESETS and LevelOrder picked
+        /// by the sender, all the peers should have picked the same
+        settings: [u8; 3],
+    },
     RelayRequestId,
     RelayIdAssigned(u64),
     RelayConnectTo(u64),
     RelayConnected,
     Input {
+        round: u8,
         // current simulation frame
         frame: u64,
-        input: Input,
+        // sender, inputs are for its slots, in the same order
+        peer: u8,
+        inputs: Vec<Input>,
     },
+    /// "by" got all the "peer" inputs for the frame
     Ack {
+        round: u8,
+        frame: u64,
+        peer: u8,
+        by: u8,
+    },
+    /// Hash of the world state right before simulating the given frame
+    /// Best effort, not re-sent, only to notice desyncs
+    Checksum {
+        round: u8,
         frame: u64,
+        // sender
+        peer: u8,
+        checksum: u64,
+    },
+    /// The sender quit the match, it is over for everyone
+    /// Sent each frame for a while, there is no ack
+    Leave {
+        peer: u8,
     },
 }
 
-pub struct Network {
-    input_scheme: InputScheme,
-
-    player1: Handle<Player>,
-    player2: Handle<Player>,
-
-    frame: u64,
+/// All the players should use the same mode, it is not negotiated over the network yet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkMode {
+    /// Wait for the remote input before simulating each frame,
+    /// with a constant input delay to hide the latency
+    DelayedLockstep,
+    /// Predict the remote input and re-simulate when the prediction was wrong
+    Rollback,
+}
 
+/// Background threads sending and receiving the messages
+/// Lives through all the rounds of the match
+pub struct Connection {
     tx: mpsc::Sender<Message>,
     rx: mpsc::Receiver<Message>,
-
-    self_id: usize,
-
-    game_started: bool,
-
-    // all the inputs from the beginning of the game
-    // will optimize memory later
-    frames_buffer: Vec<[Option<Input>; 2]>,
-    acked_frames: Vec<bool>,
 }
 
-// // get a bitmask of received remote inputs out of frames_buffer
-// fn remote_inputs_ack(remote_player_id: usize, buffer: &[[Option<Input>; 2]]) -> u8 {
-//     let mut ack = 0;
-
-//     #[allow(clippy::needless_range_loop)]
-//     for i in 0..Network::CONSTANT_DELAY as usize {
-//         if buffer[i][remote_player_id].is_some() {
-//             ack |= 1 << i;
-//         }
-//     }
-//     ack
-// }
-
-impl Network {
-    /// 8-bit bitmask is used for ACK, to make CONSTANT_DELAY more than 8
-    /// bitmask type should be changed
-    const CONSTANT_DELAY: usize = 8;
-
-    pub fn new(
-        id: usize,
-        socket: Box<dyn Socket>,
-        input_scheme: InputScheme,
-        player1: Handle<Player>,
-        player2: Handle<Player>,
-    ) -> Network {
+impl Connection {
+    pub fn new(socket: Box<dyn Socket>) -> Connection {
         let (tx, rx) = mpsc::channel::<Message>();
 
         let (tx1, rx1) = mpsc::channel::<Message>();
@@ -166,122 +224,642 @@ impl Network {
             }
         });
 
+        Connection { tx, rx: rx1 }
+    }
+}
+
+pub struct Network {
+    // one for each of our own slots
+    input_schemes: Vec<InputScheme>,
+    // indices in input_schemes of the unplugged gamepads
+    disconnected: Vec<usize>,
+    // slots of the fishes with an unplugged controller, on any peer,
+    // as of the last simulated frame
+    unplugged: Vec<usize>,
+    // own inputs of the last frame that made it into frames_buffer
+    // the frame may not advance for a while, edges are computed against this one
+    last_own_inputs: Vec<Input>,
+    mode: NetworkMode,
+
+    players: Vec<Handle<Player>>,
+    // player slots of each peer
+    slots: Vec<Range<usize>>,
+
+    round: u8,
+    frame: u64,
+
+    connection: Rc<Connection>,
+
+    self_id: usize,
+
+    game_started: bool,
+
+    // all the inputs from the beginning of the game
+    // will optimize memory later
+    frames_buffer: Vec<Vec<Option<Input>>>,
+    // which peers got our inputs for the frame
+    acked_frames: Vec<Vec<bool>>,
+
+    // Rollback only
+    // remote inputs used for the frame simulation when the real ones were not there yet
+    predictions: Vec<Vec<Option<Input>>>,
+    // amount of frames, from the very beginning, with all the remote inputs known
+    confirmed: u64,
+    // world state right before simulating the frame, for all not yet confirmed frames
+    snapshots: Vec<(u64, WorldSnapshot)>,
+
+    // Desync detection
+    // own checksums, with the world state to dump when checksums will not match
+    local_checksums: Vec<(u64, u64, WorldSnapshot)>,
+    // (frame, peer, checksum), at most one for each frame and peer
+    remote_checksums: Vec<(u64, usize, u64)>,
+    desync_frame: Option<u64>,
+
+    // we quit the match and let the others know
+    leaving: bool,
+    // the peer that quit the match
+    left: Option<usize>,
+}
+
+// // get a bitmask of received remote inputs out of frames_buffer
+// fn remote_inputs_ack(remote_player_id: usize, buffer: &[[Option<Input>; 2]]) -> u8 {
+//     let mut ack = 0;
+
+//     #[allow(clippy::needless_range_loop)]
+//     for i in 0..Network::CONSTANT_DELAY as usize {
+//         if buffer[i][remote_player_id].is_some() {
+//             ack |= 1 << i;
+//         }
+//     }
+//     ack
+// }
+
+/// A fish controller is unplugged on some peer, every peer skips the frame
+/// The inputs keep going over the network, so they all resume together
+fn paused(inputs: &[Option<Input>]) -> bool {
+    inputs.iter().any(|input| input.unwrap().unplugged)
+}
+
+impl Network {
+    /// 8-bit bitmask is used for ACK, to make CONSTANT_DELAY more than 8
+    /// bitmask type should be changed
+    const CONSTANT_DELAY: usize = 8;
+
+    /// With rollback the delay is only to make rollbacks rarer and shorter
+    const ROLLBACK_DELAY: usize = 2;
+    /// How far the simulation may go ahead of the last confirmed remote input
+    const MAX_PREDICTION: usize = 8;
+
+    /// World checksum is sent each CHECKSUM_INTERVAL frames
+    const CHECKSUM_INTERVAL: u64 = 60;
+
+    /// "peers_players" is the amount of fishes on each peer, by peer id,
+    /// "input_schemes" are for the fishes of this peer
+    pub fn new(
+        id: usize,
+        connection: Rc<Connection>,
+        round: u8,
+        mode: NetworkMode,
+        input_schemes: Vec<InputScheme>,
+        peers_players: Vec<usize>,
+        players: Vec<Handle<Player>>,
+    ) -> Network {
+        assert_eq!(input_schemes.len(), peers_players[id]);
+        assert_eq!(players.len(), peers_players.iter().sum::<usize>());
+
+        let delay = match mode {
+            NetworkMode::DelayedLockstep => Self::CONSTANT_DELAY,
+            NetworkMode::Rollback => Self::ROLLBACK_DELAY,
+        };
+
+        let mut slots = vec![];
+        let mut first_slot = 0;
+        for amount in &peers_players {
+            slots.push(first_slot..first_slot + amount);
+            first_slot += amount;
+        }
+
+        let players_count = players.len();
+        let peers_count = peers_players.len();
         let mut frames_buffer = vec![];
         let mut acked_frames = vec![];
+        let mut predictions = vec![];
 
-        // Fill first CONSTANT_DELAY frames
+        // Fill first CONSTANT_DELAY (or ROLLBACK_DELAY) frames
         // this will not really change anything - the fish will just always spend
         // first CONSTANT_DELAY frames doing nothing, not a big deal
         // But with pre-filled buffer we can avoid any special-case logic
         // at the start of the game and later on will just wait for remote
         // fish to fill up their part of the buffer
-        #[allow(clippy::needless_range_loop)]
-        for _ in 0..Self::CONSTANT_DELAY {
-            let mut frame = [None; 2];
-            frame[id as usize] = Some(Input::default());
+        for _ in 0..delay {
+            let mut frame = vec![None; players_count];
+            for slot in slots[id].clone() {
+                frame[slot] = Some(Input::default());
+            }
+            let mut acked = vec![false; peers_count];
+            acked[id] = true;
 
             frames_buffer.push(frame);
-            acked_frames.push(false);
+            acked_frames.push(acked);
+            predictions.push(vec![None; players_count]);
         }
 
         Network {
             self_id: id,
-            input_scheme,
-            player1,
-            player2,
-            frame: Self::CONSTANT_DELAY as u64,
-            tx,
-            rx: rx1,
+            last_own_inputs: vec![Input::default(); input_schemes.len()],
+            disconnected: vec![],
+            unplugged: vec![],
+            input_schemes,
+            mode,
+            players,
+            slots,
+            round,
+            frame: delay as u64,
+            connection,
             game_started: false,
             frames_buffer,
             acked_frames,
+            predictions,
+            confirmed: 0,
+            snapshots: vec![],
+            local_checksums: vec![],
+            remote_checksums: vec![],
+            desync_frame: None,
+            leaving: false,
+            left: None,
         }
     }
-}
 
-impl Node for Network {
-    fn fixed_update(mut node: RefMut<Self>) {
-        let node = &mut *node;
+    fn delay(&self) -> usize {
+        match self.mode {
+            NetworkMode::DelayedLockstep => Self::CONSTANT_DELAY,
+            NetworkMode::Rollback => Self::ROLLBACK_DELAY,
+        }
+    }
+
+    /// Our own inputs are re-sent for this many frames, remote peers can't
+    /// be further ahead of us than that either
+    fn resend_window(&self) -> usize {
+        match self.mode {
+            NetworkMode::DelayedLockstep => Self::CONSTANT_DELAY * 2,
+            NetworkMode::Rollback => (Self::ROLLBACK_DELAY + Self::MAX_PREDICTION) * 2,
+        }
+    }
+
+    fn reserve_frame(&mut self, frame: u64) {
+        if frame >= self.frames_buffer.len() as _ {
+            let players_count = self.players.len();
+            let mut acked = vec![false; self.slots.len()];
+            acked[self.self_id] = true;
+
+            self.frames_buffer
+                .resize(frame as usize + 1, vec![None; players_count]);
+            self.acked_frames.resize(frame as usize + 1, acked);
+            self.predictions
+                .resize(frame as usize + 1, vec![None; players_count]);
+        }
+    }
 
-        let own_input = input::collect_input(node.input_scheme);
+    /// Only confirmed inputs go into the replay, never the predicted ones
+    /// Paused frames are not simulated, so not recorded either
+    fn record_frame(&self, frame: u64) {
+        if paused(&self.frames_buffer[frame as usize]) {
+            return;
+        }
+
+        let inputs = self.frames_buffer[frame as usize]
+            .iter()
+            .map(|input| input.unwrap())
+            .collect::<Vec<_>>();
+
+        replay::record_frame(&inputs);
+    }
+
+    /// Stop simulating and tell the remote peers we quit the match
+    pub fn leave(&mut self) {
+        self.leaving = true;
+    }
 
-        // Right now there are only two players, so it is possible to find out
-        // remote fish id as "not ours" id. With more fish it will be more complicated
-        // and ID will be part of a protocol
-        let remote_id = if node.self_id == 1 { 0 } else { 1 };
+    /// Some remote peer quit the match, there is no way to go on without it
+    pub fn peer_left(&self) -> bool {
+        self.left.is_some()
+    }
+
+    /// All the remote peers got all our inputs so far, it is safe to leave the round
+    pub fn settled(&self) -> bool {
+        self.acked_frames[..self.frame as usize]
+            .iter()
+            .all(|acked| acked.iter().all(|acked| *acked))
+    }
+
+    /// Inputs of all the fishes are known for the frame
+    fn frame_complete(&self, frame: u64) -> bool {
+        match self.frames_buffer.get(frame as usize) {
+            Some(inputs) => inputs.iter().all(Option::is_some),
+            None => false,
+        }
+    }
+
+    /// Simulate a frame with the given inputs, unless it is paused
+    fn advance(&mut self, inputs: &[Option<Input>]) {
+        self.unplugged = (0..inputs.len())
+            .filter(|slot| inputs[*slot].unwrap().unplugged)
+            .collect();
+        if !self.unplugged.is_empty() {
+            return;
+        }
+
+        for (player, input) in self.players.iter().zip(inputs) {
+            scene::get_node(*player).apply_input(input.unwrap());
+        }
+
+        simulation::advance();
+    }
 
-        // Receive other fish input
-        while let Ok(message) = node.rx.try_recv() {
+    /// Process incoming messages.
+    /// Returns the earliest already simulated frame, simulated with
+    /// a wrongly predicted remote input
+    fn receive(&mut self) -> Option<u64> {
+        let simulated = self.frame - self.delay() as u64;
+        let mut mispredicted: Option<u64> = None;
+
+        while let Ok(message) = self.connection.rx.try_recv() {
             match message {
-                Message::Input { frame, input } => {
-                    if frame >= node.frames_buffer.len() as _ {
-                        node.frames_buffer.resize(frame as usize + 1, [None, None]);
-                        node.acked_frames.resize(frame as usize + 1, false);
+                // leftovers from the previous round
+                Message::Input { round, .. }
+                | Message::Ack { round, .. }
+                | Message::Checksum { round, .. }
+                    if round != self.round => {}
+                Message::Input {
+                    frame,
+                    peer,
+                    inputs,
+                    ..
+                } => {
+                    let remote_id = peer as usize;
+                    if remote_id == self.self_id || remote_id >= self.slots.len() {
+                        continue;
+                    }
+                    // a broken or malicious frame number would grow the buffers
+                    // without bound, a real one will be re-sent once we get closer
+                    if frame > self.frame + self.resend_window() as u64 {
+                        continue;
                     }
+                    self.reserve_frame(frame);
+
+                    for (slot, input) in self.slots[remote_id].clone().zip(inputs) {
+                        if self.frames_buffer[frame as usize][slot].is_none()
+                            && frame < simulated
+                            && self.predictions[frame as usize][slot] != Some(input)
+                        {
+                            mispredicted = Some(mispredicted.map_or(frame, |f| f.min(frame)));
+                        }
 
-                    node.frames_buffer[frame as usize][remote_id] = Some(input);
-                    node.tx.send(Message::Ack { frame }).unwrap();
+                        self.frames_buffer[frame as usize][slot] = Some(input);
+                    }
+                    self.connection
+                        .tx
+                        .send(Message::Ack {
+                            round: self.round,
+                            frame,
+                            peer,
+                            by: self.self_id as u8,
+                        })
+                        .unwrap();
+                }
+                // acks for other peers inputs are none of our business
+                Message::Ack {
+                    frame, peer, by, ..
+                } if peer as usize == self.self_id => {
+                    // an ack for a frame we never sent, or from a peer we don't know
+                    if let Some(acked) = self
+                        .acked_frames
+                        .get_mut(frame as usize)
+                        .and_then(|acked| acked.get_mut(by as usize))
+                    {
+                        *acked = true;
+                    }
                 }
-                Message::Ack { frame } => {
-                    node.acked_frames[frame as usize] = true;
+                Message::Checksum {
+                    frame,
+                    peer,
+                    checksum,
+                    ..
+                } => {
+                    let remote_id = peer as usize;
+                    if remote_id == self.self_id || remote_id >= self.slots.len() {
+                        continue;
+                    }
+                    self.remote_checksums
+                        .retain(|(f, p, _)| (*f, *p) != (frame, remote_id));
+                    self.remote_checksums.push((frame, remote_id, checksum));
+                }
+                Message::Leave { peer }
+                    if peer as usize != self.self_id && (peer as usize) < self.slots.len() =>
+                {
+                    self.left.get_or_insert(peer as usize);
                 }
                 _ => {}
             }
         }
 
-        // re-send frames missing on remote fish
-        for i in
-            (node.frame as i64 - Self::CONSTANT_DELAY as i64 * 2).max(0) as u64 as u64..node.frame
+        mispredicted
+    }
+
+    /// Run one frame of simulation, the remote input is predicted if missing
+    fn simulate(&mut self, frame: u64) {
+        self.snapshots
+            .retain(|(snapshot_frame, _)| *snapshot_frame != frame);
+        self.snapshots.push((frame, WorldSnapshot::save()));
+
+        let mut inputs = self.frames_buffer[frame as usize].clone();
+        let mut predictions = vec![None; inputs.len()];
+        for (id, input) in inputs.iter_mut().enumerate() {
+            if input.is_none() {
+                // the most basic prediction: remote fish will keep holding
+                // whatever it was holding last confirmed frame
+                // the edges are not repeated, a single press would turn into
+                // a press on every predicted frame
+                let prediction = if self.confirmed == 0 {
+                    Input::default()
+                } else {
+                    let last = self.frames_buffer[self.confirmed as usize - 1][id].unwrap();
+                    Input {
+                        pressed: Default::default(),
+                        released: Default::default(),
+                        ..last
+                    }
+                };
+
+                *input = Some(prediction);
+                predictions[id] = Some(prediction);
+            }
+        }
+        self.predictions[frame as usize] = predictions;
+
+        self.advance(&inputs);
+    }
+
+    /// Returns false if the simulation is stalled, waiting for the remote input
+    fn update_rollback(&mut self, mispredicted: Option<u64>) -> bool {
+        let simulated = self.frame - Self::ROLLBACK_DELAY as u64;
+
+        if let Some(mispredicted) = mispredicted {
+            let (_, snapshot) = self
+                .snapshots
+                .iter()
+                .find(|(frame, _)| *frame == mispredicted)
+                .expect("No snapshot for the mispredicted frame");
+            snapshot.load();
+
+            for frame in mispredicted..simulated {
+                self.simulate(frame);
+            }
+        }
+
+        while self.frame_complete(self.confirmed) {
+            self.record_frame(self.confirmed);
+            self.confirmed += 1;
+        }
+        // all the inputs before a confirmed frame are known, so its
+        // snapshot is final and will never be re-simulated again
+        let confirmed = self.confirmed;
+        let (finished, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.snapshots)
+            .into_iter()
+            .partition(|(frame, _)| *frame < confirmed);
+        self.snapshots = pending;
+        for (frame, snapshot) in finished {
+            if frame.is_multiple_of(Self::CHECKSUM_INTERVAL) {
+                self.send_checksum(frame, snapshot);
+            }
+        }
+
+        // too far ahead of the slowest remote fish, wait for it to catch up
+        if simulated >= self.confirmed + Self::MAX_PREDICTION as u64 {
+            return false;
+        }
+
+        self.simulate(simulated);
+
+        true
+    }
+
+    fn send_checksum(&mut self, frame: u64, snapshot: WorldSnapshot) {
+        let checksum = snapshot.checksum();
+
+        self.connection
+            .tx
+            .send(Message::Checksum {
+                round: self.round,
+                frame,
+                peer: self.self_id as u8,
+                checksum,
+            })
+            .unwrap();
+        self.local_checksums.push((frame, checksum, snapshot));
+    }
+
+    fn verify_checksums(&mut self) {
+        // remote checksum for those frames got lost, no need to keep them forever
+        let frame = self.frame;
+        self.local_checksums
+            .retain(|(local_frame, _, _)| local_frame + Self::CHECKSUM_INTERVAL * 10 > frame);
+
+        // and those are too old to ever get a local one to compare with
+        if let Some(oldest) = self
+            .local_checksums
+            .iter()
+            .map(|(frame, _, _)| *frame)
+            .min()
         {
-            if !node.acked_frames[i as usize] {
-                node.tx
+            self.remote_checksums
+                .retain(|(frame, _, _)| *frame >= oldest);
+        }
+
+        for (frame, peer, remote_checksum) in std::mem::take(&mut self.remote_checksums) {
+            // the local one stays, other peers will send their checksums for this frame too
+            let local = self
+                .local_checksums
+                .iter()
+                .find(|(local_frame, _, _)| *local_frame == frame);
+
+            match local {
+                Some((_, checksum, snapshot)) => {
+                    if *checksum != remote_checksum && self.desync_frame.is_none() {
+                        self.desync_frame = Some(frame);
+
+                        // remote fish will dump its own state, diff them to find
+                        // out what went wrong
+                        let path = format!("desync_{}_player{}.txt", frame, self.self_id);
+                        match snapshot.dump(&path) {
+                            Ok(()) => println!(
+                                "Desync with player {} on frame {}, state dumped to {}",
+                                peer, frame, path
+                            ),
+                            Err(err) => println!(
+                                "Desync with player {} on frame {}, failed to dump: {}",
+                                peer, frame, err
+                            ),
+                        }
+                    }
+                }
+                // we are behind, will check it later
+                None => self.remote_checksums.push((frame, peer, remote_checksum)),
+            }
+        }
+    }
+}
+
+impl Node for Network {
+    fn fixed_update(mut node: RefMut<Self>) {
+        let node = &mut *node;
+
+        if node.leaving {
+            node.connection
+                .tx
+                .send(Message::Leave {
+                    peer: node.self_id as u8,
+                })
+                .unwrap();
+            return;
+        }
+
+        node.disconnected = input::reclaim_gamepads(&mut node.input_schemes);
+
+        let own_inputs = node
+            .input_schemes
+            .iter()
+            .zip(&node.last_own_inputs)
+            .zip(node.slots[node.self_id].clone())
+            .enumerate()
+            .map(|(ix, ((input_scheme, previous), slot))| {
+                // the last state of an unplugged gamepad may be stuck, ignore it
+                if node.disconnected.contains(&ix) {
+                    return Input {
+                        unplugged: true,
+                        ..Input::default().with_edges(previous)
+                    };
+                }
+
+                let origin = scene::get_node(node.players[slot]).center();
+                input::collect_input(*input_scheme, previous, origin)
+            })
+            .collect::<Vec<_>>();
+
+        let delay = node.delay();
+
+        // Receive other fishes input
+        let mispredicted = node.receive();
+
+        // re-send frames missing on remote fish
+        let resend_window = node.resend_window();
+        for i in (node.frame as i64 - resend_window as i64).max(0) as u64..node.frame {
+            if node.acked_frames[i as usize].iter().any(|acked| !acked) {
+                let inputs = node.slots[node.self_id]
+                    .clone()
+                    .map(|slot| node.frames_buffer[i as usize][slot].unwrap())
+                    .collect();
+                node.connection
+                    .tx
                     .send(Message::Input {
+                        round: node.round,
                         frame: i,
-                        input: node.frames_buffer[i as usize][node.self_id].unwrap(),
+                        peer: node.self_id as u8,
+                        inputs,
                     })
                     .unwrap();
             }
         }
 
-        // we just received only CONSTANT_DELAY frames, assuming we certainly
-        // had remote input for all the previous frames
-        // lets double check this assumption
-        if node.frame > Self::CONSTANT_DELAY as _ {
-            for i in 0..node.frame - Self::CONSTANT_DELAY as u64 - 1 {
-                assert!(node.frames_buffer[i as usize][remote_id].is_some());
-            }
+        if !node.game_started {
+            node.game_started = (0..delay as u64).all(|i| node.frame_complete(i));
         }
 
-        if node.game_started == false {
-            let mut all_packets_received = true;
-            for i in 0..Self::CONSTANT_DELAY {
-                all_packets_received &= node.frames_buffer[i][remote_id].is_some();
-            }
-            node.game_started = all_packets_received;
+        if !node.game_started {
+            return;
         }
 
-        // we have an input for "-CONSTANT_DELAY" frame, so we can
-        // advance the simulation
-        if node.game_started {
-            if let [Some(p1_input), Some(p2_input)] =
-                node.frames_buffer[node.frame as usize - Self::CONSTANT_DELAY]
-            {
-                scene::get_node(node.player1).apply_input(p1_input);
-                scene::get_node(node.player2).apply_input(p2_input);
+        match node.mode {
+            NetworkMode::DelayedLockstep => {
+                // we just received only CONSTANT_DELAY frames, assuming we certainly
+                // had remote input for all the previous frames
+                // lets double check this assumption
+                if node.frame > delay as _ {
+                    for i in 0..node.frame - delay as u64 - 1 {
+                        assert!(node.frame_complete(i));
+                    }
+                }
 
+                // we have all the inputs for "-CONSTANT_DELAY" frame, so we can
                 // advance the simulation
-                for NodeWith { node, capability } in scene::find_nodes_with::<NetworkReplicate>() {
-                    (capability.network_update)(node);
+                let simulated = node.frame - delay as u64;
+                if !node.frame_complete(simulated) {
+                    return;
+                }
+
+                if simulated.is_multiple_of(Self::CHECKSUM_INTERVAL) {
+                    node.send_checksum(simulated, WorldSnapshot::save());
                 }
 
-                if node.frame >= node.frames_buffer.len() as _ {
-                    node.frames_buffer
-                        .resize(node.frame as usize + 1, [None, None]);
-                    node.acked_frames.resize(node.frame as usize + 1, false);
+                node.record_frame(simulated);
+                let inputs = node.frames_buffer[simulated as usize].clone();
+                node.advance(&inputs);
+            }
+            NetworkMode::Rollback => {
+                if !node.update_rollback(mispredicted) {
+                    return;
                 }
-                node.frames_buffer[node.frame as usize][node.self_id] = Some(own_input);
-                node.frame += 1;
             }
         }
+
+        node.reserve_frame(node.frame);
+        node.last_own_inputs = own_inputs.clone();
+        for (slot, input) in node.slots[node.self_id].clone().zip(own_inputs) {
+            node.frames_buffer[node.frame as usize][slot] = Some(input);
+        }
+        node.frame += 1;
+
+        node.verify_checksums();
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if !node.unplugged.is_empty() {
+            let players = node
+                .unplugged
+                .iter()
+                .map(|slot| slot + 1)
+                .collect::<Vec<_>>();
+            pause_menu::disconnected_gui(&players);
+        }
+
+        if let Some(peer) = node.left {
+            let players = node.slots[peer]
+                .clone()
+                .map(|slot| (slot + 1).to_string())
+                .collect::<Vec<_>>();
+            push_camera_state();
+            set_default_camera();
+            draw_text(
+                &format!("Player {} left the match", players.join(", ")),
+                20.,
+                60.,
+                30.,
+                color::RED,
+            );
+            pop_camera_state();
+        }
+
+        if let Some(frame) = node.desync_frame {
+            push_camera_state();
+            set_default_camera();
+            draw_text(
+                &format!("DESYNC on frame {}, state dumped", frame),
+                20.,
+                30.,
+                30.,
+                color::RED,
+            );
+            pop_camera_state();
+        }
     }
 }
@@ -2,18 +2,19 @@ use macroquad::{
     audio::{self, play_sound_once},
     color,
     experimental::{
-        animation::{AnimatedSprite, Animation},
+        animation::Animation,
         collections::storage,
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
         scene::{self, HandleUntyped, NodeWith, RefMut},
-        state_machine::{State, StateMachine},
     },
     prelude::*,
 };
 
 use crate::{
-    capabilities::{NetworkReplicate, PhysicsObject, Weapon, WeaponTrait},
-    components::PhysicsBody,
+    capabilities::{NetworkReplicate, PhysicsObject, Snapshot, Weapon, WeaponTrait},
+    components::{PhysicsBody, SimulatedSprite},
+    nodes::GameState,
+    simulation::{self, FRAME_TIME},
+    snapshot::{SnapshotReader, SnapshotWriter},
     Input, Resources,
 };
 
@@ -31,7 +32,9 @@ impl Player {
 
     pub fn pick_weapon(&mut self, weapon: NodeWith<Weapon>) {
         let resources = storage::get_mut::<Resources>();
-        play_sound_once(resources.pickup_sound);
+        if let Some(effects) = &resources.effects {
+            play_sound_once(effects.pickup_sound);
+        }
 
         self.drop_weapon();
 
@@ -43,16 +46,54 @@ impl Player {
 
         self.body.speed.y = -Self::JUMP_SPEED;
 
-        audio::play_sound(
-            resources.jump_sound,
-            audio::PlaySoundParams {
-                looped: false,
-                volume: 0.6,
-            },
-        );
+        if let Some(effects) = &resources.effects {
+            audio::play_sound(
+                effects.jump_sound,
+                audio::PlaySoundParams {
+                    looped: false,
+                    volume: 0.6,
+                },
+            );
+        }
     }
     fn slide(&mut self) {
-        self.state_machine.set_state(Self::ST_SLIDE);
+        self.set_state(Self::ST_SLIDE);
+    }
+
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    /// Switching to the current state does not restart it
+    pub fn set_state(&mut self, state: usize) {
+        if self.state != state {
+            self.state = state;
+            self.state_frames = 0;
+        }
+    }
+
+    /// Each fish gets its own spawn point at the start of the round,
+    /// levels with less spawn points than fishes will have to share
+    pub fn spawn_point(player_id: u8) -> Vec2 {
+        let resources = storage::get::<Resources>();
+        let objects = &resources.tiled_map.layers["logic"].objects;
+        let macroquad_tiled::Object {
+            world_x, world_y, ..
+        } = objects[player_id as usize % objects.len()];
+
+        vec2(world_x, world_y)
+    }
+
+    /// Back to life, empty handed
+    pub fn respawn(&mut self, pos: Vec2) {
+        self.fish_sprite.playing = true;
+        self.drop_weapon();
+
+        self.set_state(Self::ST_NORMAL);
+        self.dead = false;
+        self.eliminated = false;
+        self.body.speed = vec2(0., 0.);
+        self.body.teleport(pos);
     }
 }
 
@@ -61,18 +102,24 @@ pub struct Player {
 
     pub body: PhysicsBody,
 
-    fish_sprite: AnimatedSprite,
+    fish_sprite: SimulatedSprite,
     pub dead: bool,
+    /// Out of lives, stays dead until the next round
+    pub eliminated: bool,
     pub weapon: Option<NodeWith<Weapon>>,
+    /// Levels and pressed/released edges, see Input::with_edges
     pub input: Input,
-    pub last_frame_input: Input,
 
     jump_grace_timer: f32,
 
     was_floating: bool,
     pub floating: bool,
 
-    pub state_machine: StateMachine<Player>,
+    /// One of the ST_* constants
+    state: usize,
+    /// Simulation frames spent in the current state, all the state timings
+    /// are counted with it, so a snapshot can resume a state halfway through
+    state_frames: u32,
     pub controller_id: i32,
     pub remote_control: bool,
 
@@ -88,6 +135,9 @@ pub struct Player {
     pub incapacitated_timer: f32,
 
     pub back_armor: i32,
+    /// Shield hits left, only bullets from the front are blocked
+    /// See Bullet::update
+    pub front_armor: i32,
 }
 
 impl Player {
@@ -112,44 +162,7 @@ impl Player {
     pub const INCAPACITATED_STOP_THRESHOLD: f32 = 20.0;
 
     pub fn new(player_id: u8, controller_id: i32) -> Player {
-        let spawner_pos = {
-            let resources = storage::get_mut::<Resources>();
-            let objects = &resources.tiled_map.layers["logic"].objects;
-            let macroquad_tiled::Object {
-                world_x, world_y, ..
-            } = objects[rand::gen_range(0, objects.len()) as usize];
-            vec2(world_x, world_y)
-        };
-
-        let mut state_machine = StateMachine::new();
-        state_machine.add_state(Self::ST_NORMAL, State::new().update(Self::update_normal));
-        state_machine.add_state(
-            Self::ST_DEATH,
-            State::new().coroutine(Self::death_coroutine),
-        );
-        state_machine.add_state(
-            Self::ST_SHOOT,
-            State::new()
-                .update(Self::update_shoot)
-                .coroutine(Self::shoot_coroutine),
-        );
-        state_machine.add_state(
-            Self::ST_INCAPACITATED,
-            State::new()
-                .update(Self::update_incapacitated)
-                .coroutine(Self::incapacitated_coroutine),
-        );
-        state_machine.add_state(
-            Self::ST_AFTERMATCH,
-            State::new().update(Self::update_aftermatch),
-        );
-
-        state_machine.add_state(
-            Self::ST_SLIDE,
-            State::new()
-                .update(Self::update_slide)
-                .coroutine(Self::slide_coroutine),
-        );
+        let spawner_pos = Self::spawn_point(player_id);
 
         let body = PhysicsBody {
             collider: {
@@ -165,9 +178,11 @@ impl Player {
             last_frame_on_ground: false,
             have_gravity: true,
             bouncyness: 0.0,
+            collider_remainder: vec2(0., 0.),
+            descent_requested: false,
         };
 
-        let fish_sprite = AnimatedSprite::new(
+        let fish_sprite = SimulatedSprite::new(
             76,
             66,
             &[
@@ -220,16 +235,17 @@ impl Player {
         Player {
             id: player_id,
             dead: false,
+            eliminated: false,
             weapon: None,
             input: Default::default(),
-            last_frame_input: Default::default(),
 
             body,
             fish_sprite,
             jump_grace_timer: 0.,
             floating: false,
             was_floating: false,
-            state_machine,
+            state: Self::ST_NORMAL,
+            state_frames: 0,
             controller_id,
             remote_control: false,
             ai_enabled: false, //controller_id == 0,
@@ -237,6 +253,7 @@ impl Player {
             camera_box: Rect::new(spawner_pos.x - 30., spawner_pos.y - 150., 100., 210.),
             can_head_boink: false,
             back_armor: 0,
+            front_armor: 0,
             is_crouched: false,
             incapacitated_timer: 0.0,
             incapacitated_duration: 0.0,
@@ -244,7 +261,6 @@ impl Player {
     }
 
     pub fn apply_input(&mut self, input: Input) {
-        self.last_frame_input = self.input;
         self.input = input;
     }
 
@@ -254,42 +270,63 @@ impl Player {
         }
         self.incapacitated_duration = duration;
         self.incapacitated_timer = 0.0;
-        self.state_machine.set_state(Self::ST_INCAPACITATED);
+        self.set_state(Self::ST_INCAPACITATED);
         if should_fall {
             self.fish_sprite.set_animation(6);
         }
     }
 
-    pub fn kill(&mut self, direction: bool) {
+    /// "killer" is the id of the fish to blame, if there is one
+    pub fn kill(&mut self, direction: bool, killer: Option<u8>) {
+        // the round is over, no more killing
+        if self.state == Self::ST_AFTERMATCH {
+            return;
+        }
+
         // check if armor blocks the kill
         if direction != self.body.facing && self.back_armor > 0 {
             self.back_armor -= 1;
         } else {
             // set armor to 0
             self.back_armor = 0;
+            self.front_armor = 0;
             self.body.facing = direction;
-            if self.state_machine.state() != Self::ST_DEATH {
-                self.state_machine.set_state(Self::ST_DEATH);
+            if self.state != Self::ST_DEATH {
+                self.set_state(Self::ST_DEATH);
+                if let Some(mut game_state) = scene::find_node_by_type::<GameState>() {
+                    game_state.count_death(self.id, killer);
+                }
                 {
                     let resources = storage::get::<Resources>();
-                    play_sound_once(resources.player_die_sound);
+                    if let Some(effects) = &resources.effects {
+                        play_sound_once(effects.player_die_sound);
+                    }
                 }
             }
         }
     }
 
-    fn incapacitated_coroutine(node: &mut RefMut<Player>) -> Coroutine {
-        let _handle = node.handle();
-
-        let coroutine = async move {
-            // NOTHING HERE FOR NOW
-        };
+    /// Runs the current state, the part of network_update that differs from state to state
+    fn update_state(mut node: RefMut<Player>) {
+        let frame = node.state_frames;
+        node.state_frames += 1;
 
-        start_coroutine(coroutine)
+        match node.state {
+            Self::ST_NORMAL => Self::update_normal(&mut node),
+            Self::ST_DEATH => Self::update_death(&mut node, frame),
+            Self::ST_SHOOT => Self::update_shoot(node, frame),
+            Self::ST_SLIDE => Self::update_slide(&mut node, frame),
+            Self::ST_INCAPACITATED => Self::update_incapacitated(&mut node),
+            Self::ST_AFTERMATCH => Self::update_aftermatch(&mut node),
+            _ => unreachable!(),
+        }
     }
 
-    fn death_coroutine(node: &mut RefMut<Player>) -> Coroutine {
-        let handle = node.handle();
+    fn update_death(node: &mut RefMut<Player>, frame: u32) {
+        // give some take for a dead fish to take off the ground
+        let take_off = simulation::frames(0.1);
+        let explosion = take_off + simulation::frames(0.5);
+        let respawn = explosion + simulation::frames(0.5);
 
         let map_bottom = {
             let resources = storage::get::<Resources>();
@@ -297,154 +334,127 @@ impl Player {
             resources.tiled_map.raw_tiled_map.tileheight * resources.tiled_map.raw_tiled_map.height
         } as f32;
 
-        let coroutine = async move {
-            {
-                let mut node = scene::get_node(handle);
-                node.body.speed.x = -300. * node.body.facing_dir().x;
-                node.body.speed.y = -150.;
-                node.body.have_gravity = true;
+        if frame == 0 {
+            node.body.speed.x = -300. * node.body.facing_dir().x;
+            node.body.speed.y = -150.;
+            node.body.have_gravity = true;
 
-                node.dead = true;
-                node.fish_sprite.set_animation(2);
+            node.dead = true;
+            node.fish_sprite.set_animation(2);
 
-                // let mut score_counter = scene::get_node(node.score_counter);
-                // score_counter.count_loss(node.controller_id)
+            // already fell down the map, nothing to land on
+            if node.body.pos.y >= map_bottom {
+                node.state_frames = explosion;
             }
+        }
 
-            #[allow(clippy::blocks_in_if_conditions)]
-            if {
-                let node = scene::get_node(handle);
-                node.body.pos.y < map_bottom
-            } {
-                // give some take for a dead fish to take off the ground
-                wait_seconds(0.1).await;
-
-                // wait until it lands (or fall down the map)
-                while {
-                    let node = scene::get_node(handle);
-
-                    !(node.body.on_ground || node.body.pos.y > map_bottom)
-                } {
-                    next_frame().await;
-                }
-
-                {
-                    let mut node = scene::get_node(handle);
-                    node.fish_sprite.set_animation(3);
-                    node.body.speed = vec2(0., 0.);
-                }
-
-                wait_seconds(0.5).await;
+        if frame == take_off {
+            // wait until it lands (or fall down the map), the frame counter stays
+            // on take_off till then
+            if !(node.body.on_ground || node.body.pos.y > map_bottom) {
+                node.state_frames = take_off;
+                return;
             }
 
-            {
-                let mut resources = storage::get_mut::<Resources>();
-                let mut node = scene::get_node(handle);
-                let pos = node.body.pos;
+            node.fish_sprite.set_animation(3);
+            node.body.speed = vec2(0., 0.);
+        }
 
-                node.fish_sprite.playing = false;
-                node.body.speed = vec2(0., 0.);
-                resources.explosion_fxses.spawn(pos + vec2(15., 33.));
-            }
+        if frame == explosion {
+            let mut resources = storage::get_mut::<Resources>();
+            let pos = node.body.pos;
 
-            wait_seconds(0.5).await;
+            node.fish_sprite.playing = false;
+            node.body.speed = vec2(0., 0.);
+            if let Some(effects) = resources.effects.as_mut() {
+                effects.explosion_fxses.spawn(pos + vec2(15., 33.));
+            }
+        }
 
-            let mut this = scene::get_node(handle);
+        if frame == respawn {
+            let out_of_lives = match scene::find_node_by_type::<GameState>() {
+                Some(game_state) => game_state.out_of_lives(node.id),
+                None => false,
+            };
+            if out_of_lives {
+                node.eliminated = true;
+                return;
+            }
 
-            this.body.pos = {
+            let pos = {
                 let resources = storage::get_mut::<Resources>();
                 let objects = &resources.tiled_map.layers["logic"].objects;
                 let macroquad_tiled::Object {
                     world_x, world_y, ..
-                } = objects[rand::gen_range(0, objects.len()) as usize];
+                } = objects[simulation::gen_range(0, objects.len())];
 
                 vec2(world_x, world_y)
             };
-            this.fish_sprite.playing = true;
-            this.drop_weapon();
-
-            // in deathmatch we can just get back to normal after death
-            {
-                let mut resources = storage::get_mut::<Resources>();
-
-                this.state_machine.set_state(Self::ST_NORMAL);
-                this.dead = false;
-                resources
-                    .collision_world
-                    .set_actor_position(this.body.collider, this.body.pos);
-            }
-        };
-
-        start_coroutine(coroutine)
-    }
-
-    fn shoot_coroutine(node: &mut RefMut<Player>) -> Coroutine {
-        if let Some(ref weapon) = node.weapon {
-            weapon.shoot(node.handle())
-        } else {
-            let handle = node.handle();
-
-            start_coroutine(async move {
-                let player = &mut *scene::get_node(handle);
-                player.state_machine.set_state(Player::ST_NORMAL);
-            })
+            node.respawn(pos);
         }
     }
 
-    fn update_incapacitated(node: &mut RefMut<Player>, dt: f32) {
-        node.incapacitated_timer += dt;
+    fn update_incapacitated(node: &mut RefMut<Player>) {
+        node.incapacitated_timer += FRAME_TIME;
         if node.incapacitated_timer >= node.incapacitated_duration {
             node.incapacitated_timer = 0.0;
             node.incapacitated_duration = 0.0;
-            node.state_machine.set_state(Player::ST_NORMAL);
+            node.set_state(Player::ST_NORMAL);
         }
     }
 
-    fn update_shoot(node: &mut RefMut<Player>, _dt: f32) {
+    /// Weapons borrow the fish while shooting, so this one takes the node by value
+    fn update_shoot(mut node: RefMut<Player>, frame: u32) {
         node.body.speed.x *= 0.9;
-    }
-
-    fn update_aftermatch(node: &mut RefMut<Player>, _dt: f32) {
-        node.body.speed.x = 0.0;
-    }
 
-    fn update_slide(_node: &mut RefMut<Player>, _dt: f32) {}
-
-    fn slide_coroutine(node: &mut RefMut<Player>) -> Coroutine {
+        let (weapon, shoot) = match node.weapon.as_ref() {
+            Some(weapon) => (weapon.node, weapon.capability.shoot),
+            None => {
+                node.set_state(Player::ST_NORMAL);
+                return;
+            }
+        };
         let handle = node.handle();
+        drop(node);
 
-        let coroutine = async move {
-            {
-                let mut node = scene::get_node(handle);
-                node.body.speed.x = if node.body.facing {
-                    Self::SLIDE_SPEED
-                } else {
-                    -Self::SLIDE_SPEED
-                };
-                node.fish_sprite.set_animation(6);
+        if shoot(weapon, handle, frame) {
+            let mut node = scene::get_node(handle);
+            if node.state == Player::ST_SHOOT {
+                node.set_state(Player::ST_NORMAL);
             }
+        }
+    }
 
-            wait_seconds(Self::SLIDE_DURATION).await;
+    fn update_aftermatch(node: &mut RefMut<Player>) {
+        node.body.speed.x = 0.0;
+    }
 
-            {
-                let mut node = scene::get_node(handle);
-                node.state_machine.set_state(Self::ST_NORMAL);
-            }
-        };
+    fn update_slide(node: &mut RefMut<Player>, frame: u32) {
+        if frame == 0 {
+            node.body.speed.x = if node.body.facing {
+                Self::SLIDE_SPEED
+            } else {
+                -Self::SLIDE_SPEED
+            };
+            node.fish_sprite.set_animation(6);
+        }
 
-        start_coroutine(coroutine)
+        if frame + 1 >= simulation::frames(Self::SLIDE_DURATION) {
+            node.set_state(Self::ST_NORMAL);
+        }
     }
-    fn update_normal(node: &mut RefMut<Player>, _dt: f32) {
+
+    fn update_normal(node: &mut RefMut<Player>) {
         if node.remote_control {
             return;
         }
 
         // self destruct, for debugging only
         if is_key_pressed(KeyCode::Y) {
-            node.kill(true);
+            node.kill(true, None);
         }
         if is_key_pressed(KeyCode::U) {
-            node.kill(false);
+            node.kill(false, None);
         }
 
         let node_handle = node.handle();
@@ -493,11 +503,7 @@ impl Player {
         }
 
         // if in jump and want to jump again
-        if !node.body.on_ground
-            && node.input.jump
-            && !node.last_frame_input.jump
-            && node.jump_grace_timer <= 0.0
-        {
+        if !node.body.on_ground && node.input.pressed.jump && node.jump_grace_timer <= 0.0 {
             //
             if !node.was_floating {
                 node.floating = true;
@@ -506,7 +512,7 @@ impl Player {
             }
         }
         // jump button released, stop to float
-        if node.floating && !node.input.jump && node.last_frame_input.jump {
+        if node.floating && node.input.released.jump {
             node.floating = false;
         }
         if node.body.on_ground {
@@ -523,9 +529,9 @@ impl Player {
 
         if node.body.on_ground {
             if node.input.down {
-                if node.input.jump && !node.last_frame_input.jump {
+                if node.input.pressed.jump {
                     node.body.descent();
-                } else if node.input.slide && !node.last_frame_input.slide {
+                } else if node.input.pressed.slide {
                     node.slide();
                 }
             }
@@ -533,23 +539,21 @@ impl Player {
             node.body.descent();
         }
 
-        if !node.input.down
-            && node.input.jump
-            && !node.last_frame_input.jump
-            && node.jump_grace_timer > 0.
-        {
+        if !node.input.down && node.input.pressed.jump && node.jump_grace_timer > 0. {
             node.jump_grace_timer = 0.0;
 
             node.jump();
         }
 
-        if node.input.throw && !node.last_frame_input.throw {
+        if node.input.pressed.throw {
             if let Some(weapon) = node.weapon.as_mut() {
                 weapon.throw(!node.input.down);
                 node.weapon = None;
                 {
                     let resources = storage::get::<Resources>();
-                    play_sound_once(resources.player_throw_sound);
+                    if let Some(effects) = &resources.effects {
+                        play_sound_once(effects.player_throw_sound);
+                    }
                 }
 
                 // when the flocating fish is throwing a weapon and keeps
@@ -583,14 +587,20 @@ impl Player {
         if node.input.fire {
             //
             if node.weapon.is_some() {
-                node.state_machine.set_state(Self::ST_SHOOT);
+                node.set_state(Self::ST_SHOOT);
                 node.floating = false;
             }
         }
     }
 
+    /// Hitbox center, weapons aim from here
+    pub fn center(&self) -> Vec2 {
+        let hitbox = self.get_hitbox();
+        hitbox.point() + hitbox.size() / 2.
+    }
+
     pub fn get_hitbox(&self) -> Rect {
-        let state = self.state_machine.state();
+        let state = self.state;
         Rect::new(
             self.body.pos.x,
             if state == Self::ST_INCAPACITATED || state == Self::ST_SLIDE || self.is_crouched {
@@ -613,7 +623,7 @@ impl Player {
 
     fn network_update(mut node: RefMut<Self>) {
         // Break incapacitated
-        if node.state_machine.state() == Player::ST_INCAPACITATED && node.body.speed.x != 0.0 {
+        if node.state == Player::ST_INCAPACITATED && node.body.speed.x != 0.0 {
             if node.body.speed.x > Player::INCAPACITATED_STOP_THRESHOLD
                 || node.body.speed.x < -Player::INCAPACITATED_STOP_THRESHOLD
             {
@@ -623,10 +633,10 @@ impl Player {
             }
         }
 
-        {
-            // if scene::get_node(node.game_state).game_paused {
-            //     return;
-            // }
+        if let Some(game_state) = scene::find_node_by_type::<GameState>() {
+            if game_state.game_paused {
+                return;
+            }
         }
 
         node.fish_sprite.update();
@@ -638,20 +648,21 @@ impl Player {
         } as f32;
 
         if node.body.pos.y > map_bottom {
-            node.kill(false);
+            node.kill(false, None);
         }
 
         {
             let node = &mut *node;
             if let Some(weapon) = node.weapon.as_mut() {
                 weapon.mount(node.body.pos, node.body.facing);
+                weapon.aim(node.input.aim);
             }
         }
 
         if node.ai_enabled {
             let mut ai = node.ai.take().unwrap();
             let input = ai.update(&mut *node);
-            node.input = input;
+            node.input = input.with_edges(&node.input);
             node.ai = Some(ai);
         }
 
@@ -668,7 +679,7 @@ impl Player {
             if node.body.on_ground && !node.input.jump {
                 node.jump_grace_timer = Self::JUMP_GRACE_TIME;
             } else if node.jump_grace_timer > 0. {
-                node.jump_grace_timer -= get_frame_time();
+                node.jump_grace_timer -= FRAME_TIME;
             }
 
             node.body.update();
@@ -683,8 +694,10 @@ impl Player {
                     //
                     if hitbox.y + 60.0 < other_hitbox.y + Self::HEAD_THRESHOLD {
                         let resources = storage::get_mut::<Resources>();
-                        play_sound_once(resources.jump_sound);
-                        other.kill(!node.body.facing);
+                        if let Some(effects) = &resources.effects {
+                            play_sound_once(effects.jump_sound);
+                        }
+                        other.kill(!node.body.facing, Some(node.id));
                     }
                 }
             }
@@ -708,7 +721,7 @@ impl Player {
             }
         }
 
-        StateMachine::update_detached(node, |node| &mut node.state_machine);
+        Self::update_state(node);
     }
 }
 
@@ -716,25 +729,26 @@ impl scene::Node for Player {
     fn ready(mut node: RefMut<Self>) {
         node.provides(Self::physics_capabilities());
         node.provides(Self::network_capabilities());
+        node.provides(Self::snapshot_capabilities());
     }
 
     fn draw(node: RefMut<Self>) {
+        if node.eliminated {
+            return;
+        }
+
         let resources = storage::get::<Resources>();
 
         draw_texture_ex(
-            if node.controller_id == 0 {
-                if node.can_head_boink {
-                    resources.whale_boots_blue
-                } else {
-                    resources.whale_blue
-                }
-            } else {
-                //
-                if node.can_head_boink {
-                    resources.whale_boots_green
-                } else {
-                    resources.whale_green
-                }
+            match (node.id % 4, node.can_head_boink) {
+                (0, true) => resources.whale_boots_blue,
+                (0, false) => resources.whale_blue,
+                (1, true) => resources.whale_boots_green,
+                (1, false) => resources.whale_green,
+                (2, true) => resources.whale_boots_orange,
+                (2, false) => resources.whale_orange,
+                (_, true) => resources.whale_boots_purple,
+                (_, false) => resources.whale_purple,
             },
             node.body.pos.x - 25.,
             node.body.pos.y - 10.,
@@ -765,6 +779,24 @@ impl scene::Node for Player {
                 },
             )
         }
+
+        // and the shield in front
+        if node.front_armor > 0 {
+            draw_texture_ex(
+                if node.front_armor == 1 {
+                    resources.broken_shield
+                } else {
+                    resources.shield
+                },
+                node.body.pos.x + if node.body.facing { 15.0 } else { -20.0 },
+                node.body.pos.y,
+                color::WHITE,
+                DrawTextureParams {
+                    flip_x: !node.body.facing,
+                    ..Default::default()
+                },
+            )
+        }
     }
 
     fn update(mut node: RefMut<Self>) {
@@ -826,4 +858,63 @@ impl Player {
 
         NetworkReplicate { network_update }
     }
+
+    fn snapshot_capabilities() -> Snapshot {
+        fn save(handle: HandleUntyped, writer: &mut SnapshotWriter) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Player>();
+
+            node.body.save(writer);
+            node.fish_sprite.save(writer);
+            writer.write(&node.dead);
+            writer.write(&node.eliminated);
+            writer.write_node(node.weapon.as_ref().map(|weapon| weapon.node));
+            writer.write(&node.input);
+            writer.write(&node.jump_grace_timer);
+            writer.write(&node.was_floating);
+            writer.write(&node.floating);
+            writer.write(&(node.state as u32));
+            writer.write(&node.state_frames);
+            writer.write_rect(node.camera_box);
+            writer.write(&node.can_head_boink);
+            writer.write(&node.is_crouched);
+            writer.write(&node.incapacitated_duration);
+            writer.write(&node.incapacitated_timer);
+            writer.write(&node.back_armor);
+            writer.write(&node.front_armor);
+        }
+
+        fn load(handle: HandleUntyped, reader: &mut SnapshotReader) {
+            let mut node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Player>();
+
+            node.body.load(reader);
+            node.fish_sprite.load(reader);
+            node.dead = reader.read();
+            node.eliminated = reader.read();
+            node.weapon = reader.read_node_with::<Weapon>();
+            node.input = reader.read();
+            node.jump_grace_timer = reader.read();
+            node.was_floating = reader.read();
+            node.floating = reader.read();
+            node.state = reader.read::<u32>() as usize;
+            node.state_frames = reader.read();
+
+            node.camera_box = reader.read_rect();
+            node.can_head_boink = reader.read();
+            node.is_crouched = reader.read();
+            node.incapacitated_duration = reader.read();
+            node.incapacitated_timer = reader.read();
+            node.back_armor = reader.read();
+            node.front_armor = reader.read();
+        }
+
+        Snapshot {
+            save,
+            load,
+            rebuild: None,
+        }
+    }
 }
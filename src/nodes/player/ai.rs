@@ -1,13 +1,12 @@
 use macroquad::{
     experimental::{collections::storage, scene},
     math::vec2,
-    rand,
-    time::get_frame_time,
 };
 
 use crate::{
     capabilities::Weapon,
     nodes::{player::Input, Player},
+    simulation::{self, FRAME_TIME},
     Resources,
 };
 
@@ -57,7 +56,11 @@ impl Ai {
             && (player.body.pos.y - foe.body.pos.y).abs() >= 50.
             && !following_horiz
         {
-            self.fix_direction = if rand::gen_range(0, 2) == 0 { 1 } else { -1 };
+            self.fix_direction = if simulation::gen_range(0, 2) == 0 {
+                1
+            } else {
+                -1
+            };
             self.keep_direction_until_event = true;
         }
 
@@ -93,32 +96,36 @@ impl Ai {
             }
         }
 
-        if rand::gen_range(0, 200) == 5 {
-            self.fix_direction = if rand::gen_range(0, 2) == 0 { 1 } else { -1 };
+        if simulation::gen_range(0, 200) == 5 {
+            self.fix_direction = if simulation::gen_range(0, 2) == 0 {
+                1
+            } else {
+                -1
+            };
             self.keep_direction_until_event = true;
         }
 
-        if rand::gen_range(0, 800) == 5 {
+        if simulation::gen_range(0, 800) == 5 {
             input.throw = true;
             self.throw_cooldown = 1.;
         }
 
-        if player.body.pos.distance(foe.body.pos) <= 100. || rand::gen_range(0, 180) == 5 {
+        if player.body.pos.distance(foe.body.pos) <= 100. || simulation::gen_range(0, 180) == 5 {
             //
-            if player.state_machine.state() == Player::ST_NORMAL && player.weapon.is_some() {
-                player.state_machine.set_state(Player::ST_SHOOT);
+            if player.state() == Player::ST_NORMAL && player.weapon.is_some() {
+                player.set_state(Player::ST_SHOOT);
             }
         }
 
         if self.jump_cooldown >= 0. {
-            self.jump_cooldown -= get_frame_time();
+            self.jump_cooldown -= FRAME_TIME;
         }
         if self.throw_cooldown >= 0. {
-            self.throw_cooldown -= get_frame_time();
+            self.throw_cooldown -= FRAME_TIME;
         }
 
         if self.keep_direction_timeout >= 0. {
-            self.keep_direction_timeout -= get_frame_time();
+            self.keep_direction_timeout -= FRAME_TIME;
         }
 
         if self.throw_cooldown <= 0.0 {
@@ -0,0 +1,114 @@
+//! Node feeding the players with inputs from a replay file
+//!
+//! P - pause, N - one frame forward while paused, -/= - slower/faster
+
+use macroquad::{
+    color,
+    experimental::scene::{self, Handle, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{input::Input, nodes::Player, replay, simulation};
+
+pub struct ReplayPlayback {
+    frames: Vec<Vec<Input>>,
+    players: Vec<Handle<Player>>,
+
+    frame: usize,
+    paused: bool,
+    step_requested: bool,
+    speed: usize,
+    // simulation frames owed to the fixed_update calls, for non-integer speeds
+    accumulator: f32,
+}
+
+impl ReplayPlayback {
+    const SPEEDS: [f32; 6] = [0.1, 0.25, 0.5, 1., 2., 4.];
+    const NORMAL_SPEED: usize = 3;
+
+    pub fn new(replay: replay::Replay, players: Vec<Handle<Player>>) -> ReplayPlayback {
+        ReplayPlayback {
+            frames: replay.frames,
+            players,
+            frame: 0,
+            paused: false,
+            step_requested: false,
+            speed: Self::NORMAL_SPEED,
+            accumulator: 0.,
+        }
+    }
+
+    fn step(&mut self) {
+        for (player, input) in self.players.iter().zip(&self.frames[self.frame]) {
+            scene::get_node(*player).apply_input(*input);
+        }
+
+        simulation::advance();
+
+        self.frame += 1;
+    }
+}
+
+impl Node for ReplayPlayback {
+    fn update(mut node: RefMut<Self>) {
+        if is_key_pressed(KeyCode::P) {
+            node.paused ^= true;
+        }
+        if is_key_pressed(KeyCode::N) {
+            node.step_requested = true;
+        }
+        if is_key_pressed(KeyCode::Minus) && node.speed > 0 {
+            node.speed -= 1;
+        }
+        if is_key_pressed(KeyCode::Equal) && node.speed < Self::SPEEDS.len() - 1 {
+            node.speed += 1;
+        }
+    }
+
+    fn fixed_update(mut node: RefMut<Self>) {
+        let steps = if node.paused {
+            node.accumulator = 0.;
+            std::mem::take(&mut node.step_requested) as u32
+        } else {
+            node.accumulator += Self::SPEEDS[node.speed];
+            let steps = node.accumulator.floor();
+            node.accumulator -= steps;
+            steps as u32
+        };
+
+        for _ in 0..steps {
+            if node.frame >= node.frames.len() {
+                break;
+            }
+            node.step();
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        push_camera_state();
+        set_default_camera();
+
+        let status = if node.frame >= node.frames.len() {
+            "finished"
+        } else if node.paused {
+            "paused"
+        } else {
+            ""
+        };
+        draw_text(
+            &format!(
+                "Replay {}/{} x{} {}",
+                node.frame,
+                node.frames.len(),
+                Self::SPEEDS[node.speed],
+                status
+            ),
+            20.,
+            30.,
+            30.,
+            color::WHITE,
+        );
+
+        pop_camera_state();
+    }
+}
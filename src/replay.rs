@@ -0,0 +1,128 @@
+//! Match recording and playback
+//!
+//! Simulation is deterministic, so the map, the RNG seed, the item layout
+//! and all the inputs are enough to get exactly the same match again.
+//! Well, as long as all the items involved are deterministic - see "network_ready".
+//!
+//! File is a header followed by one record per simulation frame, written as the
+//! match goes, so even a match that crashed the game can be replayed.
+
+use macroquad::experimental::collections::storage;
+
+use nanoserde::{DeBin, SerBin};
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{input::Input, nodes::MatchRules};
+
+#[derive(Debug, Clone, DeBin, SerBin)]
+pub struct ReplayItem {
+    /// "tiled_name" of the item
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, DeBin, SerBin)]
+pub struct ReplayHeader {
+    pub map: String,
+    pub seed: u64,
+    pub players: u32,
+    pub rules: MatchRules,
+    /// Score after the previous rounds of the match, a replay is just one round
+    pub round_wins: Vec<u32>,
+    pub items: Vec<ReplayItem>,
+}
+
+#[derive(Clone)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    /// Inputs of all the players, for each simulation frame
+    pub frames: Vec<Vec<Input>>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let data = std::fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        let mut offset = 0;
+        let header = ReplayHeader::de_bin(&mut offset, &data)
+            .map_err(|err| format!("{}: broken header: {:?}", path, err))?;
+
+        let mut frames = vec![];
+        while offset < data.len() {
+            // the last frame may be cut in half if the game was killed
+            match Vec::<Input>::de_bin(&mut offset, &data) {
+                Ok(inputs) => frames.push(inputs),
+                Err(_) => break,
+            }
+        }
+
+        Ok(Replay { header, frames })
+    }
+}
+
+const FLUSH_INTERVAL: u64 = 60;
+
+struct ReplayRecorder {
+    file: Option<BufWriter<File>>,
+    frame: u64,
+}
+
+/// Start writing a new replay into "replays" folder
+/// Replays are nice to have, not being able to write one is not an error
+pub fn start_recording(header: &ReplayHeader) {
+    let file = (|| -> std::io::Result<BufWriter<File>> {
+        std::fs::create_dir_all("replays")?;
+
+        let path = format!("replays/{}.replay", macroquad::miniquad::date::now() as u64);
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(&SerBin::serialize_bin(header))?;
+
+        println!("Recording replay to {}", path);
+        Ok(file)
+    })();
+
+    let file = match file {
+        Ok(file) => Some(file),
+        Err(err) => {
+            println!("Replay will not be recorded: {}", err);
+            None
+        }
+    };
+
+    storage::store(ReplayRecorder { file, frame: 0 });
+}
+
+pub fn stop_recording() {
+    storage::store(ReplayRecorder {
+        file: None,
+        frame: 0,
+    });
+}
+
+/// Final inputs of all the players for the next simulation frame
+/// Should be called once per frame, in the simulation order
+pub fn record_frame(inputs: &[Input]) {
+    let mut recorder = match storage::try_get_mut::<ReplayRecorder>() {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let recorder = &mut *recorder;
+
+    if let Some(file) = recorder.file.as_mut() {
+        let mut result = file.write_all(&SerBin::serialize_bin(&inputs.to_vec()));
+        if recorder.frame % FLUSH_INTERVAL == 0 {
+            result = result.and_then(|_| file.flush());
+        }
+
+        if let Err(err) = result {
+            println!("Replay recording stopped: {}", err);
+            recorder.file = None;
+        }
+    }
+    recorder.frame += 1;
+}
@@ -0,0 +1,144 @@
+//! Fixed timestep simulation clock
+//!
+//! Everything called from "network_update" should measure time with this module,
+//! not with macroquad's get_frame_time/wait_seconds.
+//! Timings are counted in frames, with counters kept in the snapshot -
+//! coroutines are not an option, their progress can't be saved or rolled back.
+//! Each network_update is exactly one FRAME_TIME long, no matter how fast the game
+//! is actually running on each peer - the only way to keep the peers in sync.
+//!
+//! Same goes for random: simulation should use only the match RNG from this module,
+//! seeded with the seed agreed by all the peers.
+
+use macroquad::experimental::{
+    collections::storage,
+    scene::{self, NodeWith},
+};
+
+use crate::capabilities::NetworkReplicate;
+
+pub const FRAMES_PER_SECOND: u32 = 60;
+pub const FRAME_TIME: f32 = 1. / FRAMES_PER_SECOND as f32;
+
+/// Amount of simulation frames covering the given amount of seconds
+pub fn frames(seconds: f32) -> u32 {
+    (seconds * FRAMES_PER_SECOND as f32).ceil() as u32
+}
+
+/// Advance the whole simulation by one frame
+pub fn advance() {
+    for NodeWith { node, capability } in scene::find_nodes_with::<NetworkReplicate>() {
+        (capability.network_update)(node);
+    }
+}
+
+/// Match-scoped random number generator
+/// Same PCG as macroquad's "rand", but with its own state, so menus, particles
+/// and other non-simulation random calls can't make the peers diverge
+pub struct MatchRng {
+    state: u64,
+}
+
+impl MatchRng {
+    const MULTIPLIER: u64 = 6364136223846793005;
+    const INCREMENT: u64 = 1442695040888963407;
+
+    pub fn new(seed: u64) -> MatchRng {
+        let mut rng = MatchRng { state: 0 };
+        rng.next();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next();
+        rng
+    }
+
+    fn next(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(Self::INCREMENT);
+
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        let rot = (state >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Random number in [0..1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+pub trait RandomRange {
+    fn gen_range(rng: &mut MatchRng, low: Self, high: Self) -> Self;
+}
+
+impl RandomRange for f32 {
+    fn gen_range(rng: &mut MatchRng, low: f32, high: f32) -> f32 {
+        low + (high - low) * rng.next_f32()
+    }
+}
+
+// Empty ranges give "low", like macroquad's rand::gen_range
+
+impl RandomRange for i32 {
+    fn gen_range(rng: &mut MatchRng, low: i32, high: i32) -> i32 {
+        if high <= low {
+            return low;
+        }
+
+        let range = (high as i64 - low as i64) as u64;
+        (low as i64 + (rng.next() as u64 % range) as i64) as i32
+    }
+}
+
+impl RandomRange for usize {
+    fn gen_range(rng: &mut MatchRng, low: usize, high: usize) -> usize {
+        if high <= low {
+            return low;
+        }
+
+        low + rng.next() as usize % (high - low)
+    }
+}
+
+/// Start a new match RNG, should be called before spawning anything
+pub fn srand(seed: u64) {
+    storage::store(MatchRng::new(seed));
+}
+
+/// Random number in [low..high) range, from the match RNG
+pub fn gen_range<T: RandomRange>(low: T, high: T) -> T {
+    let mut rng = storage::get_mut::<MatchRng>();
+
+    T::gen_range(&mut rng, low, high)
+}
+
+/// Everything needed to restore the RNG to the current point, for snapshots
+pub fn rng_state() -> u64 {
+    storage::get::<MatchRng>().state
+}
+
+pub fn set_rng_state(state: u64) {
+    storage::get_mut::<MatchRng>().state = state;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchRng, RandomRange};
+
+    #[test]
+    fn empty_ranges() {
+        let mut rng = MatchRng::new(1);
+
+        assert_eq!(usize::gen_range(&mut rng, 0, 0), 0);
+        assert_eq!(usize::gen_range(&mut rng, 5, 2), 5);
+        assert_eq!(i32::gen_range(&mut rng, 3, 3), 3);
+        assert_eq!(i32::gen_range(&mut rng, 3, -3), 3);
+
+        for _ in 0..100 {
+            let n = i32::gen_range(&mut rng, i32::MIN, i32::MAX);
+            assert!(n < i32::MAX);
+            assert!((0..7).contains(&usize::gen_range(&mut rng, 0, 7)));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Saving and restoring the simulation state
+//!
+//! Each node with a "Snapshot" capability writes its own state into a byte buffer,
+//! a world snapshot is just a list of such buffers.
+//! References between nodes (a fish holding a sword, a sword knowing its owner)
+//! are stored as indices in this list, so the buffers do not depend on
+//! macroquad's scene handles.
+//!
+//! The scene is restored as a whole: nodes spawned after the snapshot are deleted
+//! and nodes deleted after the snapshot are rebuilt.
+//! Collision world actors are owned by PhysicsBody components, and each body
+//! restores its own actor, so loading a world snapshot restores the collision world
+//! as well.
+//!
+//! Everything that changes from frame to frame should be in a snapshot, including
+//! "how far along" things are: state frame counters, sprite timers. That's why
+//! there are no coroutines in the simulation - a coroutine's progress can't be saved,
+//! after loading it would either restart or keep running from the wrong point.
+
+use macroquad::{
+    experimental::scene::{self, HandleUntyped, NodeWith},
+    math::{vec2, Rect, Vec2},
+};
+
+use nanoserde::{DeBin, SerBin};
+
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{capabilities::Snapshot, simulation};
+
+/// HandleUntyped is neither comparable nor hashable, but its Debug output
+/// is a complete "id + generation" pair
+fn handle_key(handle: HandleUntyped) -> String {
+    format!("{:?}", handle)
+}
+
+pub struct SnapshotWriter<'a> {
+    data: Vec<u8>,
+    nodes: &'a HashMap<String, u32>,
+}
+
+impl<'a> SnapshotWriter<'a> {
+    pub fn write<T: SerBin>(&mut self, value: &T) {
+        value.ser_bin(&mut self.data);
+    }
+
+    pub fn write_vec2(&mut self, value: Vec2) {
+        self.write(&value.x);
+        self.write(&value.y);
+    }
+
+    pub fn write_rect(&mut self, value: Rect) {
+        self.write(&value.x);
+        self.write(&value.y);
+        self.write(&value.w);
+        self.write(&value.h);
+    }
+
+    /// Reference to another node, the node should be a part of the same snapshot
+    pub fn write_node(&mut self, node: Option<HandleUntyped>) {
+        let ix = match node {
+            Some(node) => *self
+                .nodes
+                .get(&handle_key(node))
+                .expect("Referenced node does not provide Snapshot capability"),
+            None => u32::MAX,
+        };
+
+        self.write(&ix);
+    }
+}
+
+pub struct SnapshotReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    nodes: &'a [HandleUntyped],
+}
+
+impl<'a> SnapshotReader<'a> {
+    pub fn read<T: DeBin>(&mut self) -> T {
+        DeBin::de_bin(&mut self.offset, self.data).unwrap()
+    }
+
+    pub fn read_vec2(&mut self) -> Vec2 {
+        let x = self.read();
+        let y = self.read();
+
+        vec2(x, y)
+    }
+
+    pub fn read_rect(&mut self) -> Rect {
+        let x = self.read();
+        let y = self.read();
+        let w = self.read();
+        let h = self.read();
+
+        Rect::new(x, y, w, h)
+    }
+
+    pub fn read_node(&mut self) -> Option<HandleUntyped> {
+        let ix: u32 = self.read();
+
+        if ix == u32::MAX {
+            return None;
+        }
+
+        let handle = self.nodes[ix as usize];
+
+        // the node may be deleted since the snapshot was made
+        scene::get_untyped_node(handle).map(|_| handle)
+    }
+
+    /// Same as "read_node", but also looks up a node's capability
+    pub fn read_node_with<T: Any + Copy>(&mut self) -> Option<NodeWith<T>> {
+        let handle = self.read_node()?;
+        let key = handle_key(handle);
+
+        scene::find_nodes_with::<T>().find(|node| handle_key(node.node) == key)
+    }
+}
+
+/// State of all the nodes providing "Snapshot" capability
+/// and of the match RNG
+pub struct WorldSnapshot {
+    nodes: Vec<(HandleUntyped, Snapshot, Vec<u8>)>,
+    rng_state: u64,
+}
+
+impl WorldSnapshot {
+    pub fn save() -> WorldSnapshot {
+        let snapshot_nodes = scene::find_nodes_with::<Snapshot>().collect::<Vec<_>>();
+        let indices = snapshot_nodes
+            .iter()
+            .enumerate()
+            .map(|(ix, node)| (handle_key(node.node), ix as u32))
+            .collect::<HashMap<_, _>>();
+
+        let nodes = snapshot_nodes
+            .into_iter()
+            .map(|NodeWith { node, capability }| {
+                let mut writer = SnapshotWriter {
+                    data: vec![],
+                    nodes: &indices,
+                };
+                (capability.save)(node, &mut writer);
+
+                (node, capability, writer.data)
+            })
+            .collect();
+
+        WorldSnapshot {
+            nodes,
+            rng_state: simulation::rng_state(),
+        }
+    }
+
+    /// Hash of the whole simulation state, equal worlds give equal checksums
+    /// FNV-1a, to not depend on std hasher implementation details
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        feed(&self.rng_state.to_le_bytes());
+        for (_, _, data) in &self.nodes {
+            feed(data);
+        }
+
+        hash
+    }
+
+    /// Human-diffable dump of the whole state, one line per node
+    pub fn dump(&self, path: &str) -> std::io::Result<()> {
+        use std::fmt::Write;
+
+        let mut dump = String::new();
+        writeln!(dump, "rng: {}", self.rng_state).unwrap();
+        for (ix, (handle, _, data)) in self.nodes.iter().enumerate() {
+            write!(dump, "{} {:?}:", ix, handle).unwrap();
+            for byte in data {
+                write!(dump, " {:02x}", byte).unwrap();
+            }
+            writeln!(dump).unwrap();
+        }
+
+        std::fs::write(path, dump)
+    }
+
+    pub fn load(&self) {
+        let mut handles = self
+            .nodes
+            .iter()
+            .map(|(handle, _, _)| *handle)
+            .collect::<Vec<_>>();
+        let saved = handles
+            .iter()
+            .map(|handle| handle_key(*handle))
+            .collect::<HashSet<_>>();
+
+        // everything spawned after the snapshot never happened
+        let spawned = scene::find_nodes_with::<Snapshot>()
+            .filter(|node| !saved.contains(&handle_key(node.node)))
+            .map(|node| node.node)
+            .collect::<Vec<_>>();
+        for node in spawned {
+            if let Some(node) = scene::get_untyped_node(node) {
+                node.delete();
+            }
+        }
+
+        // and everything deleted after the snapshot should be back
+        // all the nodes are rebuilt before loading any data, so references
+        // between nodes will point to the new handles
+        for (handle, (_, capability, _)) in handles.iter_mut().zip(&self.nodes) {
+            if scene::get_untyped_node(*handle).is_none() {
+                let rebuild = capability
+                    .rebuild
+                    .expect("Node was deleted, but can't be rebuilt from a snapshot");
+
+                *handle = rebuild();
+            }
+        }
+
+        for (handle, (_, capability, data)) in handles.iter().zip(&self.nodes) {
+            let mut reader = SnapshotReader {
+                data,
+                offset: 0,
+                nodes: &handles,
+            };
+            (capability.load)(*handle, &mut reader);
+        }
+
+        // after rebuilding, in case some constructor used the RNG
+        simulation::set_rng_state(self.rng_state);
+    }
+}